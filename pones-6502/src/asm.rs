@@ -0,0 +1,720 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::cpu::{Bus, CpuVariant};
+use crate::opcodes::AddrMode;
+
+/// A run of assembled bytes starting at `addr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// The output of [`assemble`].
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    /// The assembled bytes, in source order. Each `.org` starts a new chunk.
+    pub chunks: Vec<Chunk>,
+    symbols: HashMap<String, i64>,
+}
+
+impl Assembly {
+    /// The value of a label or constant.
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).map(|&value| value as u16)
+    }
+
+    /// Write every chunk to the bus.
+    pub fn write_to(&self, bus: &mut impl Bus) {
+        for chunk in &self.chunks {
+            for (i, &byte) in chunk.bytes.iter().enumerate() {
+                bus.write(chunk.addr.wrapping_add(i as u16), byte);
+            }
+        }
+    }
+}
+
+/// Returned by [`assemble`] for the first line that can't be assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// The line number, starting from 1.
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    Syntax(&'static str),
+    UnknownMnemonic(String),
+    UnknownDirective(String),
+    /// The instruction doesn't have the addressing mode its operand was written in.
+    InvalidAddressingMode(String),
+    UndefinedSymbol(String),
+    DuplicateSymbol(String),
+    /// A value doesn't fit in its operand or directive.
+    OutOfRange(i64),
+    /// A branch target is further away than a signed byte can reach.
+    BranchOutOfRange(i64),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::Syntax(message) => write!(f, "{}", message),
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic {}", name),
+            AsmErrorKind::UnknownDirective(name) => write!(f, "unknown directive {}", name),
+            AsmErrorKind::InvalidAddressingMode(name) => write!(f, "invalid addressing mode for {}", name),
+            AsmErrorKind::UndefinedSymbol(name) => write!(f, "undefined symbol {}", name),
+            AsmErrorKind::DuplicateSymbol(name) => write!(f, "symbol {} is already defined", name),
+            AsmErrorKind::OutOfRange(value) => write!(f, "value {} is out of range", value),
+            AsmErrorKind::BranchOutOfRange(offset) => write!(f, "branch offset {} is out of range", offset),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assemble `source` for `variant` in two passes, so labels can be used before they're defined.
+///
+/// The syntax follows ca65:
+/// - Labels end with a colon, which can be left out if the label starts the line.
+///   Constants are defined with `name = expr`.
+/// - Operands are written as `#expr`, `expr`, `expr,X`, `(expr,X)`, `(expr),Y`, `(expr)`,
+///   `A` or nothing for the accumulator, and `zp,target` for `BBR`/`BBS`.
+///   Zero page addressing is used when the value is known to fit, unless prefixed with `a:`.
+/// - Expressions have numbers (`$hex`, `%binary`, decimal, `'c'`), symbols, `*` for the
+///   current address, the unary operators `-`, `~`, `<` (low byte) and `>` (high byte), and
+///   the binary operators `* / & ^ << >>`, then `+ - |`, then the comparisons `= <> < > <= >=`,
+///   from highest to lowest precedence. Comparisons are 1 if true and 0 if false.
+/// - The directives are `.org addr`, `.byte` with values or strings, `.word`,
+///   `.res count[,fill]`, `.if expr`/`.else`/`.endif` for conditional assembly,
+///   and `.end`, which ignores the rest of the source.
+///
+/// Mnemonics, directives and registers are case insensitive, and `;` starts a comment.
+pub fn assemble(variant: CpuVariant, source: &str) -> Result<Assembly, AsmError> {
+    let mut asm = Assembler {
+        variant,
+        symbols: HashMap::new(),
+        modes: Vec::new(),
+        final_pass: false,
+        pc: 0,
+        chunks: Vec::new(),
+        instructions: 0,
+        unknown: None,
+        conditions: Vec::new(),
+    };
+    asm.pass(source)?;
+    asm.final_pass = true;
+    asm.pass(source)?;
+    asm.chunks.retain(|chunk| !chunk.bytes.is_empty());
+    Ok(Assembly { chunks: asm.chunks, symbols: asm.symbols })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Directive(String),
+    Number(i64),
+    Str(Vec<u8>),
+    Punct(char),
+    Shl,
+    Shr,
+    Ne,
+    Le,
+    Ge,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, AsmErrorKind> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    let word_end = |start: usize| {
+        line[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(line.len(), |len| start + len)
+    };
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            ';' => break,
+            _ if c.is_whitespace() => continue,
+            _ if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                let end = word_end(i + 1);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+                match c {
+                    '.' => Token::Directive(line[i + 1..end].into()),
+                    _ => Token::Ident(line[i..end].into()),
+                }
+            }
+            '$' | '%' | '0'..='9' => {
+                let (start, radix) = match c {
+                    '$' => (i + 1, 16),
+                    '%' => (i + 1, 2),
+                    _ => (i, 10),
+                };
+                let end = word_end(start);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+                let value = i64::from_str_radix(&line[start..end], radix)
+                    .map_err(|_| AsmErrorKind::Syntax("invalid number"))?;
+                Token::Number(value)
+            }
+            '\'' => match (chars.next(), chars.next()) {
+                (Some((_, c)), Some((_, '\''))) if c.is_ascii() => Token::Number(c as i64),
+                _ => return Err(AsmErrorKind::Syntax("invalid character constant")),
+            },
+            '"' => {
+                let mut string = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) if c.is_ascii() => string.push(c as u8),
+                        Some(_) => return Err(AsmErrorKind::Syntax("strings must be ASCII")),
+                        None => return Err(AsmErrorKind::Syntax("unterminated string")),
+                    }
+                }
+                Token::Str(string)
+            }
+            '<' if chars.next_if(|&(_, c)| c == '<').is_some() => Token::Shl,
+            '>' if chars.next_if(|&(_, c)| c == '>').is_some() => Token::Shr,
+            '<' if chars.next_if(|&(_, c)| c == '>').is_some() => Token::Ne,
+            '<' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Le,
+            '>' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Ge,
+            '#' | '(' | ')' | ',' | ':' | '=' | '+' | '-' | '*' | '/' | '&' | '|' | '^' | '~' | '<' | '>' => {
+                Token::Punct(c)
+            }
+            _ => return Err(AsmErrorKind::Syntax("unexpected character")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Cursor<'t> {
+    tokens: &'t [Token],
+    pos: usize,
+}
+
+impl<'t> Cursor<'t> {
+    fn peek(&self) -> Option<&'t Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&'t Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<&'t Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), AsmErrorKind> {
+        match self.next() {
+            Some(Token::Punct(p)) if *p == c => Ok(()),
+            _ => Err(AsmErrorKind::Syntax("unbalanced parentheses")),
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), AsmErrorKind> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(AsmErrorKind::Syntax("unexpected text after the operand")),
+        }
+    }
+}
+
+fn is_register(token: &Token, name: &str) -> bool {
+    matches!(token, Token::Ident(ident) if ident.eq_ignore_ascii_case(name))
+}
+
+// The precedence of binary operators, which is the same as in ca65.
+fn precedence(token: &Token) -> Option<u8> {
+    match token {
+        Token::Punct('=' | '<' | '>') | Token::Ne | Token::Le | Token::Ge => Some(0),
+        Token::Punct('+' | '-' | '|') => Some(1),
+        Token::Punct('*' | '/' | '&' | '^') | Token::Shl | Token::Shr => Some(2),
+        _ => None,
+    }
+}
+
+fn apply(op: &Token, lhs: i64, rhs: i64) -> Result<i64, AsmErrorKind> {
+    Ok(match op {
+        Token::Punct('=') => (lhs == rhs) as i64,
+        Token::Ne => (lhs != rhs) as i64,
+        Token::Punct('<') => (lhs < rhs) as i64,
+        Token::Punct('>') => (lhs > rhs) as i64,
+        Token::Le => (lhs <= rhs) as i64,
+        Token::Ge => (lhs >= rhs) as i64,
+        Token::Punct('|') => lhs | rhs,
+        Token::Punct('^') => lhs ^ rhs,
+        Token::Punct('&') => lhs & rhs,
+        Token::Shl => lhs.wrapping_shl(rhs as u32),
+        Token::Shr => lhs.wrapping_shr(rhs as u32),
+        Token::Punct('+') => lhs.wrapping_add(rhs),
+        Token::Punct('-') => lhs.wrapping_sub(rhs),
+        Token::Punct('*') => lhs.wrapping_mul(rhs),
+        _ => lhs.checked_div(rhs).ok_or(AsmErrorKind::Syntax("division by zero"))?,
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Index {
+    X,
+    Y,
+}
+
+// An operand as written. Values are `None` if they use symbols that
+// haven't been defined yet in the first pass.
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Option<i64>),
+    Direct { value: Option<i64>, index: Option<Index>, absolute: bool },
+    Indirect(Option<i64>),
+    IndexedIndirect(Option<i64>),
+    IndirectIndexed(Option<i64>),
+    BitBranch(Option<i64>, Option<i64>),
+}
+
+struct Assembler {
+    variant: CpuVariant,
+    symbols: HashMap<String, i64>,
+    // The addressing mode of each instruction chosen in the first pass,
+    // so the final pass lays everything out at the same addresses.
+    modes: Vec<AddrMode>,
+    final_pass: bool,
+    pc: u16,
+    chunks: Vec<Chunk>,
+    // The number of instructions assembled so far in this pass.
+    instructions: usize,
+    // The first undefined symbol used by the current expression in the first pass.
+    unknown: Option<String>,
+    // Each `.if` being assembled, as whether the enclosing block is assembled
+    // and whether the current branch's condition holds.
+    conditions: Vec<(bool, bool)>,
+}
+
+impl Assembler {
+    fn pass(&mut self, source: &str) -> Result<(), AsmError> {
+        self.pc = 0;
+        self.chunks.clear();
+        self.instructions = 0;
+        let mut lines = 0;
+        for (i, line) in source.lines().enumerate() {
+            lines = i + 1;
+            match self.line(line) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(kind) => return Err(AsmError { line: lines, kind }),
+            }
+        }
+        if !self.conditions.is_empty() {
+            return Err(AsmError { line: lines, kind: AsmErrorKind::Syntax("missing .endif") });
+        }
+        Ok(())
+    }
+
+    fn assembling(&self) -> bool {
+        self.conditions.last().is_none_or(|&(enclosing, condition)| enclosing && condition)
+    }
+
+    // Handles `.if`, `.else` and `.endif`, returning `false` for any other line.
+    fn conditional(&mut self, c: &mut Cursor) -> Result<bool, AsmErrorKind> {
+        let Some(Token::Directive(name)) = c.peek() else {
+            return Ok(false);
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "if" => {
+                c.next();
+                let enclosing = self.assembling();
+                // Conditions in blocks that aren't assembled may use symbols that don't exist.
+                let condition = enclosing && self.known_expr(c)? != 0;
+                c.expect_end()?;
+                self.conditions.push((enclosing, condition));
+            }
+            "else" => {
+                let (_, condition) = self.conditions.last_mut()
+                    .ok_or(AsmErrorKind::Syntax(".else without .if"))?;
+                *condition = !*condition;
+            }
+            "endif" => {
+                self.conditions.pop().ok_or(AsmErrorKind::Syntax(".endif without .if"))?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Returns `false` at the end of the source.
+    fn line(&mut self, line: &str) -> Result<bool, AsmErrorKind> {
+        let tokens = tokenize(line)?;
+        let mut c = Cursor { tokens: &tokens, pos: 0 };
+        let starts_line = line.starts_with(|c: char| !c.is_whitespace());
+        if self.conditional(&mut c)? || !self.assembling() {
+            return Ok(true);
+        }
+
+        if let Some(Token::Ident(name)) = c.peek() {
+            match c.peek_at(1) {
+                Some(Token::Punct('=')) => {
+                    c.pos = 2;
+                    self.unknown = None;
+                    let value = self.expr(&mut c)?;
+                    c.expect_end()?;
+                    if let Some(value) = value {
+                        self.define(name, value)?;
+                    }
+                    return Ok(true);
+                }
+                Some(Token::Punct(':')) => {
+                    c.pos = 2;
+                    self.define(name, self.pc as i64)?;
+                }
+                _ if starts_line && !self.is_mnemonic(name) => {
+                    c.pos = 1;
+                    self.define(name, self.pc as i64)?;
+                }
+                _ => {}
+            }
+        }
+
+        match c.next() {
+            None => Ok(true),
+            Some(Token::Directive(name)) => self.directive(name, &mut c),
+            Some(Token::Ident(mnemonic)) => {
+                self.instruction(mnemonic, &mut c)?;
+                Ok(true)
+            }
+            Some(_) => Err(AsmErrorKind::Syntax("expected an instruction or directive")),
+        }
+    }
+
+    fn define(&mut self, name: &str, value: i64) -> Result<(), AsmErrorKind> {
+        if self.symbols.insert(name.into(), value).is_some() && !self.final_pass {
+            return Err(AsmErrorKind::DuplicateSymbol(name.into()));
+        }
+        Ok(())
+    }
+
+    fn is_mnemonic(&self, name: &str) -> bool {
+        self.variant.opcodes().iter().any(|info| info.mnemonic().eq_ignore_ascii_case(name))
+    }
+
+    fn find_opcode(&self, mnemonic: &str, mode: AddrMode) -> Option<u8> {
+        let find = |official_only: bool| {
+            self.variant.opcodes().iter().position(|info| {
+                info.mnemonic() == mnemonic && info.mode == mode && (info.official || !official_only)
+            })
+        };
+        // Prefer documented opcodes, such as $EA over the illegal NOPs.
+        find(true).or_else(|| find(false)).map(|opcode| opcode as u8)
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        if self.chunks.is_empty() {
+            self.chunks.push(Chunk { addr: self.pc, bytes: Vec::new() });
+        }
+        self.chunks.last_mut().unwrap().bytes.extend_from_slice(bytes);
+        self.pc = self.pc.wrapping_add(bytes.len() as u16);
+    }
+
+    // Range checks are left to the final pass, once every symbol is known.
+    fn check(&self, value: Option<i64>, range: RangeInclusive<i64>) -> Result<i64, AsmErrorKind> {
+        let value = value.unwrap_or(0);
+        if self.final_pass && !range.contains(&value) {
+            return Err(AsmErrorKind::OutOfRange(value));
+        }
+        Ok(value)
+    }
+
+    fn directive(&mut self, name: &str, c: &mut Cursor) -> Result<bool, AsmErrorKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "org" => {
+                let addr = self.known_expr(c)?;
+                c.expect_end()?;
+                if !(0..=0xFFFF).contains(&addr) {
+                    return Err(AsmErrorKind::OutOfRange(addr));
+                }
+                self.pc = addr as u16;
+                match self.chunks.last_mut() {
+                    Some(chunk) if chunk.bytes.is_empty() => chunk.addr = self.pc,
+                    _ => self.chunks.push(Chunk { addr: self.pc, bytes: Vec::new() }),
+                }
+            }
+            "byte" | "word" => {
+                let word = name.eq_ignore_ascii_case("word");
+                loop {
+                    match c.peek() {
+                        Some(Token::Str(string)) if !word => {
+                            c.next();
+                            self.emit(string);
+                        }
+                        _ if word => {
+                            let value = self.expr(c)?;
+                            let value = self.check(value, -0x8000..=0xFFFF)?;
+                            self.emit(&(value as u16).to_le_bytes());
+                        }
+                        _ => {
+                            let value = self.expr(c)?;
+                            let value = self.check(value, -0x80..=0xFF)?;
+                            self.emit(&[value as u8]);
+                        }
+                    }
+                    match c.next() {
+                        None => break,
+                        Some(Token::Punct(',')) => {}
+                        Some(_) => return Err(AsmErrorKind::Syntax("expected a comma")),
+                    }
+                }
+            }
+            "res" => {
+                let count = self.known_expr(c)?;
+                if !(0..=0x10000).contains(&count) {
+                    return Err(AsmErrorKind::OutOfRange(count));
+                }
+                let fill = match c.next() {
+                    None => 0,
+                    Some(Token::Punct(',')) => {
+                        let fill = self.expr(c)?;
+                        self.check(fill, -0x80..=0xFF)? as u8
+                    }
+                    Some(_) => return Err(AsmErrorKind::Syntax("expected a comma")),
+                };
+                c.expect_end()?;
+                self.emit(&vec![fill; count as usize]);
+            }
+            "end" => return Ok(false),
+            _ => return Err(AsmErrorKind::UnknownDirective(format!(".{}", name))),
+        }
+        Ok(true)
+    }
+
+    fn instruction(&mut self, mnemonic: &str, c: &mut Cursor) -> Result<(), AsmErrorKind> {
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        if !self.is_mnemonic(&mnemonic) {
+            return Err(AsmErrorKind::UnknownMnemonic(mnemonic));
+        }
+        let operand = self.operand(c)?;
+        let mode = if self.final_pass {
+            self.modes[self.instructions]
+        } else {
+            let mode = self.choose_mode(&mnemonic, &operand)?;
+            self.modes.push(mode);
+            mode
+        };
+        self.instructions += 1;
+        let opcode = self.find_opcode(&mnemonic, mode)
+            .ok_or(AsmErrorKind::InvalidAddressingMode(mnemonic))?;
+
+        let addr = self.pc;
+        let branch_offset = |this: &Self, target: Option<i64>, len: u16| {
+            let offset = target.unwrap_or(0) - addr.wrapping_add(len) as i64;
+            if this.final_pass && !(-0x80..=0x7F).contains(&offset) {
+                return Err(AsmErrorKind::BranchOutOfRange(offset));
+            }
+            Ok(offset as u8)
+        };
+        let value = match operand {
+            Operand::None | Operand::Accumulator => None,
+            Operand::Immediate(value)
+            | Operand::Direct { value, .. }
+            | Operand::Indirect(value)
+            | Operand::IndexedIndirect(value)
+            | Operand::IndirectIndexed(value)
+            | Operand::BitBranch(value, _) => value,
+        };
+        let operand_bytes = match mode {
+            AddrMode::Implied | AddrMode::Accumulator => vec![],
+            AddrMode::Relative => vec![branch_offset(self, value, 2)?],
+            AddrMode::ZeroPageRelative => {
+                let Operand::BitBranch(_, target) = operand else { unreachable!() };
+                vec![self.check(value, 0..=0xFF)? as u8, branch_offset(self, target, 3)?]
+            }
+            AddrMode::Immediate => vec![self.check(value, -0x80..=0xFF)? as u8],
+            AddrMode::ZeroPage
+            | AddrMode::ZeroPageX
+            | AddrMode::ZeroPageY
+            | AddrMode::IndexedIndirect
+            | AddrMode::IndirectIndexed
+            | AddrMode::ZeroPageIndirect => vec![self.check(value, 0..=0xFF)? as u8],
+            AddrMode::Absolute
+            | AddrMode::AbsoluteX
+            | AddrMode::AbsoluteY
+            | AddrMode::Indirect
+            | AddrMode::AbsoluteIndexedIndirect => (self.check(value, 0..=0xFFFF)? as u16).to_le_bytes().to_vec(),
+        };
+        self.emit(&[opcode]);
+        self.emit(&operand_bytes);
+        Ok(())
+    }
+
+    fn operand(&mut self, c: &mut Cursor) -> Result<Operand, AsmErrorKind> {
+        self.unknown = None;
+        let operand = match (c.peek(), c.peek_at(1)) {
+            (None, _) => Operand::None,
+            (Some(token), None) if is_register(token, "a") => {
+                c.next();
+                Operand::Accumulator
+            }
+            (Some(Token::Punct('#')), _) => {
+                c.next();
+                Operand::Immediate(self.expr(c)?)
+            }
+            (Some(Token::Punct('(')), _) if self.is_indirect(c) => self.indirect_operand(c)?,
+            (Some(prefix), Some(Token::Punct(':'))) if is_register(prefix, "a") => {
+                c.pos += 2;
+                self.direct_operand(c, true)?
+            }
+            _ => self.direct_operand(c, false)?,
+        };
+        c.expect_end()?;
+        Ok(operand)
+    }
+
+    // Whether the operand is an indirect one, rather than an expression starting with a parenthesis.
+    fn is_indirect(&self, c: &Cursor) -> bool {
+        let rest = &c.tokens[c.pos..];
+        match matching_paren(rest) {
+            Some(close) => match &rest[close + 1..] {
+                [] => true,
+                [Token::Punct(','), index] => is_register(index, "y"),
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    fn indirect_operand(&mut self, c: &mut Cursor) -> Result<Operand, AsmErrorKind> {
+        let rest = &c.tokens[c.pos..];
+        let close = matching_paren(rest).unwrap();
+        let inside = &rest[1..close];
+        let (expr, kind): (_, fn(_) -> _) = match (inside, &rest[close + 1..]) {
+            ([expr @ .., Token::Punct(','), index], []) if is_register(index, "x") => (expr, Operand::IndexedIndirect),
+            (_, []) => (inside, Operand::Indirect),
+            _ => (inside, Operand::IndirectIndexed),
+        };
+        let mut inner = Cursor { tokens: expr, pos: 0 };
+        let value = self.expr(&mut inner)?;
+        inner.expect_end()?;
+        c.pos = c.tokens.len();
+        Ok(kind(value))
+    }
+
+    fn direct_operand(&mut self, c: &mut Cursor, absolute: bool) -> Result<Operand, AsmErrorKind> {
+        let value = self.expr(c)?;
+        if c.peek() != Some(&Token::Punct(',')) {
+            return Ok(Operand::Direct { value, index: None, absolute });
+        }
+        c.next();
+        let index = match (c.peek(), c.peek_at(1)) {
+            (Some(token), None) if is_register(token, "x") => Index::X,
+            (Some(token), None) if is_register(token, "y") => Index::Y,
+            _ => return Ok(Operand::BitBranch(value, self.expr(c)?)),
+        };
+        c.next();
+        Ok(Operand::Direct { value, index: Some(index), absolute })
+    }
+
+    fn choose_mode(&self, mnemonic: &str, operand: &Operand) -> Result<AddrMode, AsmErrorKind> {
+        use AddrMode::*;
+
+        let has = |mode| self.find_opcode(mnemonic, mode).is_some();
+        let sized = |zero_page, absolute| match operand {
+            Operand::Direct { absolute: true, .. } => vec![absolute],
+            Operand::Direct { value: Some(0..=0xFF), .. } => vec![zero_page, absolute],
+            _ => vec![absolute, zero_page],
+        };
+        let candidates = match operand {
+            Operand::None => vec![Implied, Accumulator],
+            Operand::Accumulator => vec![Accumulator],
+            Operand::Immediate(_) => vec![Immediate],
+            Operand::Direct { index: None, .. } if has(Relative) => vec![Relative],
+            Operand::Direct { index: None, .. } => sized(ZeroPage, Absolute),
+            Operand::Direct { index: Some(Index::X), .. } => sized(ZeroPageX, AbsoluteX),
+            Operand::Direct { index: Some(Index::Y), .. } => sized(ZeroPageY, AbsoluteY),
+            Operand::Indirect(_) => vec![Indirect, ZeroPageIndirect],
+            Operand::IndexedIndirect(_) => vec![IndexedIndirect, AbsoluteIndexedIndirect],
+            Operand::IndirectIndexed(_) => vec![IndirectIndexed],
+            Operand::BitBranch(..) => vec![ZeroPageRelative],
+        };
+        candidates.into_iter().find(|&mode| has(mode))
+            .ok_or_else(|| AsmErrorKind::InvalidAddressingMode(mnemonic.into()))
+    }
+
+    // For directives that need to know the value in the first pass.
+    fn known_expr(&mut self, c: &mut Cursor) -> Result<i64, AsmErrorKind> {
+        self.unknown = None;
+        let value = self.expr(c)?;
+        value.ok_or_else(|| AsmErrorKind::UndefinedSymbol(self.unknown.take().unwrap_or_default()))
+    }
+
+    fn expr(&mut self, c: &mut Cursor) -> Result<Option<i64>, AsmErrorKind> {
+        self.binary_expr(c, 0)
+    }
+
+    fn binary_expr(&mut self, c: &mut Cursor, min_precedence: u8) -> Result<Option<i64>, AsmErrorKind> {
+        let mut lhs = self.unary_expr(c)?;
+        while let Some(op) = c.peek().filter(|op| precedence(op).is_some_and(|p| p >= min_precedence)) {
+            c.next();
+            let rhs = self.binary_expr(c, precedence(op).unwrap() + 1)?;
+            lhs = match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) => Some(apply(op, lhs, rhs)?),
+                _ => None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary_expr(&mut self, c: &mut Cursor) -> Result<Option<i64>, AsmErrorKind> {
+        let op: fn(i64) -> i64 = match c.peek() {
+            Some(Token::Punct('-')) => |value| value.wrapping_neg(),
+            Some(Token::Punct('~')) => |value| !value,
+            Some(Token::Punct('<')) => |value| value & 0xFF,
+            Some(Token::Punct('>')) => |value| (value >> 8) & 0xFF,
+            _ => return self.primary_expr(c),
+        };
+        c.next();
+        Ok(self.unary_expr(c)?.map(op))
+    }
+
+    fn primary_expr(&mut self, c: &mut Cursor) -> Result<Option<i64>, AsmErrorKind> {
+        match c.next() {
+            Some(Token::Number(value)) => Ok(Some(*value)),
+            Some(Token::Punct('*')) => Ok(Some(self.pc as i64)),
+            Some(Token::Punct('(')) => {
+                let value = self.expr(c)?;
+                c.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) => match self.symbols.get(name) {
+                Some(&value) => Ok(Some(value)),
+                None if self.final_pass => Err(AsmErrorKind::UndefinedSymbol(name.clone())),
+                None => {
+                    self.unknown.get_or_insert_with(|| name.clone());
+                    Ok(None)
+                }
+            },
+            _ => Err(AsmErrorKind::Syntax("expected an expression")),
+        }
+    }
+}
+
+// The index of the parenthesis closing the one at the start of `tokens`.
+fn matching_paren(tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
@@ -1,4 +1,5 @@
 use crate::reg_state::RegisterState;
+use crate::opcodes::{cmos_opcodes, nmos_opcodes, OpcodeInfo, CMOS_OPCODES, NMOS_OPCODES};
 
 const STACK_BASE: u8 = 0x01;
 const VECTOR_BASE: u8 = 0xFF;
@@ -6,17 +7,175 @@ const IRQ_BRK_VECTOR: u8 = 0xFE;
 const RESET_VECTOR: u8 = 0xFC;
 const NMI_VECTOR: u8 = 0xFA;
 
+/// Returned by [`Cpu6502::step`] once the CPU has been jammed by one of the
+/// `STP`/`KIL` opcodes. Only a reset will get the CPU running again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuHalted {
+    /// The address of the opcode that jammed the CPU.
+    pub pc: u16,
+}
+
+impl std::fmt::Display for CpuHalted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cpu halted by opcode at {:#06X}", self.pc)
+    }
+}
+
+impl std::error::Error for CpuHalted {}
+
 pub trait Bus {
     fn read(&mut self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, value: u8);
+
+    /// Read a value without any side effects, for debugging tools.
+    /// Returns `None` if that isn't possible, such as for I/O registers
+    /// that change state when read. Defaults to `None` for every address.
+    fn peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// Like [`Bus::read`], but told what the CPU is going to do with the value. The CPU
+    /// makes all of its reads through this, so tools like code/data loggers can tell
+    /// code from data. Defaults to [`Bus::read`].
+    fn read_as(&mut self, addr: u16, _kind: ReadKind) -> u8 {
+        self.read(addr)
+    }
+
+    /// The level of the NMI line, for systems where it's driven by a device on the bus.
+    /// The CPU samples it at every interrupt poll, and again partway through BRK and
+    /// interrupt sequences, so that an NMI raised during one can hijack it. Defaults to
+    /// `None`, leaving the line to [`Cpu6502::set_nmi`].
+    fn nmi_line(&mut self) -> Option<bool> {
+        None
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// What the CPU does with a value it reads. See [`Bus::read_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReadKind {
+    /// The first byte of an instruction. The real chip signals these on its SYNC pin.
+    Opcode,
+    /// The bytes of an instruction after the opcode.
+    Operand,
+    /// Any other read whose value is used, including pointers, the stack and vectors.
+    Data,
+    /// A read whose value is thrown away. These are only made when the CPU is cycle accurate.
+    Dummy,
+}
+
+/// The chip being emulated. Each host system should pick the chip it actually uses,
+/// since they differ in ways software can observe.
+///
+/// Only the differences described for each variant are modelled. All of them share the
+/// NMOS 6502's interrupt timing, and the unstable `SHA`, `SHX`, `SHY` and `TAS` opcodes
+/// behave the same on every NMOS variant. The only unstable opcodes that vary are
+/// `XAA #i` and `LXA #i`, through [`CpuVariant::default_unstable_magic`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CpuVariant {
+    /// The original NMOS 6502, including its illegal opcodes and the `JMP ($xxFF)` bug.
+    #[default]
+    Nmos6502,
+    /// The Ricoh 2A03/2A07 in the NES, an NMOS 6502 with decimal mode removed.
+    /// The `D` flag can still be set, but `ADC` and `SBC` ignore it.
+    Ricoh2A03,
+    /// The 6507 in the Atari 2600, an NMOS 6502 with only 13 address lines and no
+    /// interrupt pins. Addresses are mirrored every 8 KiB, and the IRQ and NMI lines
+    /// can't be asserted. `BRK` still works.
+    Mos6507,
+    /// The WDC 65C02, which adds instructions and addressing modes, fixes the
+    /// NMOS bugs, turns the illegal opcodes into NOPs, and clears `D` on interrupts.
+    Wdc65C02,
+}
+
+impl CpuVariant {
+    /// Information about every opcode of the chip, indexed by opcode.
+    pub fn opcodes(self) -> &'static [OpcodeInfo; 256] {
+        match self {
+            Self::Wdc65C02 => &CMOS_OPCODES,
+            _ => &NMOS_OPCODES,
+        }
+    }
+
+    pub fn has_decimal_mode(self) -> bool {
+        self != Self::Ricoh2A03
+    }
+
+    pub fn has_interrupt_lines(self) -> bool {
+        self != Self::Mos6507
+    }
+
+    /// The address lines the chip has, as a mask applied to every bus access.
+    pub fn address_mask(self) -> u16 {
+        match self {
+            Self::Mos6507 => 0x1FFF,
+            _ => 0xFFFF,
+        }
+    }
+
+    /// The magic constant usually observed for the chip's unstable `XAA #i` and `LXA #i`
+    /// opcodes. The 2A03 is commonly seen to use `$FF`, which makes `LXA #i` load both A and X.
+    pub fn default_unstable_magic(self) -> u8 {
+        match self {
+            Self::Ricoh2A03 => 0xFF,
+            _ => DEFAULT_UNSTABLE_MAGIC,
+        }
+    }
+}
+
+/// The value commonly observed for the analog "magic constant" ORed into A by the unstable
+/// `XAA #i` and `LXA #i` opcodes. The real value varies between chips and even temperature.
+pub const DEFAULT_UNSTABLE_MAGIC: u8 = 0xEE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cpu6502 {
     pub reg: RegisterState,
     pub sp: u8,
     pub pc: u16,
-    pub decimal_mode_disabled: bool,
+    pub variant: CpuVariant,
+    /// Forward the accesses the hardware discards (dummy reads on indexed addressing
+    /// and implied ops, dummy writes on read-modify-write ops) to the bus, reproducing
+    /// the exact per-cycle bus access sequence. Disabled by default.
+    pub cycle_accurate: bool,
+    /// The magic constant used by the unstable `XAA #i` and `LXA #i` opcodes.
+    pub unstable_magic: u8,
+    /// Total number of cycles executed since the CPU was created.
+    pub cycles: u64,
+    pub(crate) halted: Option<CpuHalted>,
+    pub(crate) irq_sources: u8,
+    pub(crate) nmi_line: bool,
+    // Set on a rising edge of the NMI line, and cleared once the NMI is serviced.
+    pub(crate) nmi_pending: bool,
+    // CLI, SEI and PLP change the interrupt disable flag after interrupts
+    // have been polled, so the poll sees the flag from before the instruction.
+    pub(crate) interrupt_disable_at_poll: Option<bool>,
+    // Set when the next interrupt poll is skipped.
+    pub(crate) skip_interrupt_poll: bool,
+    // Set by the 65C02's WAI until an interrupt line is asserted.
+    pub(crate) waiting: bool,
+}
+
+impl Default for Cpu6502 {
+    fn default() -> Self {
+        Self {
+            reg: RegisterState::default(),
+            sp: 0,
+            pc: 0,
+            variant: CpuVariant::default(),
+            cycle_accurate: false,
+            unstable_magic: DEFAULT_UNSTABLE_MAGIC,
+            cycles: 0,
+            halted: None,
+            irq_sources: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            interrupt_disable_at_poll: None,
+            skip_interrupt_poll: false,
+            waiting: false,
+        }
+    }
 }
 
 impl Cpu6502 {
@@ -24,72 +183,340 @@ impl Cpu6502 {
         Self::default()
     }
 
+    #[deprecated = "use `Cpu6502::with_variant(CpuVariant::Ricoh2A03)`"]
     pub fn with_no_decimal() -> Self {
+        Self::with_variant(CpuVariant::Ricoh2A03)
+    }
+
+    /// Create a CPU for `variant`, using the variant's default unstable magic constant.
+    pub fn with_variant(variant: CpuVariant) -> Self {
         let mut this = Self::new();
-        this.decimal_mode_disabled = true;
+        this.variant = variant;
+        this.unstable_magic = variant.default_unstable_magic();
         this
     }
 
-    pub fn step(&mut self, bus: &mut impl Bus) {
-        CpuWithBus { cpu: self, bus }.step()
+    /// Whether the CPU has been jammed by a `STP`/`KIL` opcode.
+    pub fn is_halted(&self) -> bool {
+        self.halted.is_some()
+    }
+
+    /// Whether the CPU is stopped by the 65C02's `WAI` until an interrupt line is asserted.
+    /// Each step spends a single cycle while waiting.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Execute one instruction, returning the number of cycles it took.
+    ///
+    /// Returns `Err` when the instruction is a `STP`/`KIL` opcode. The opcode is still
+    /// fetched from the bus, but its cycles aren't reported, and PC is left pointing at it.
+    /// From then on the CPU is halted: every call returns the same `Err` straight away,
+    /// without touching the bus or taking interrupts, until [`Cpu6502::reset`].
+    ///
+    /// If an interrupt was detected by the previous instruction,
+    /// the step runs the interrupt sequence instead.
+    pub fn step(&mut self, bus: &mut impl Bus) -> Result<u32, CpuHalted> {
+        if let Some(halted) = self.halted {
+            return Err(halted);
+        }
+        let cycles = self.count_cycles(|cpu| CpuWithBus::new(cpu, bus).step());
+        match self.halted {
+            Some(halted) => Err(halted),
+            None => Ok(cycles),
+        }
+    }
+
+    /// Run the reset sequence, returning the number of cycles it took.
+    /// This also recovers the CPU if it was halted.
+    pub fn reset(&mut self, bus: &mut impl Bus) -> u32 {
+        self.halted = None;
+        self.count_cycles(|cpu| CpuWithBus::new(cpu, bus).reset())
+    }
+
+    /// Assert or release the IRQ line for the sources in the bitmask `sources`. The line is
+    /// level sensitive, and stays asserted for as long as any source is asserting it.
+    /// The bits can be assigned to sources however the system sees fit.
+    /// Has no effect if the variant has no interrupt lines.
+    pub fn set_irq(&mut self, sources: u8, asserted: bool) {
+        if !self.variant.has_interrupt_lines() {
+            return;
+        }
+        if asserted {
+            self.irq_sources |= sources;
+        } else {
+            self.irq_sources &= !sources;
+        }
+    }
+
+    /// The sources currently asserting the IRQ line.
+    pub fn irq_sources(&self) -> u8 {
+        self.irq_sources
+    }
+
+    /// Set the level of the NMI line. An NMI is triggered when the line
+    /// becomes asserted, and holding it asserted doesn't trigger any more.
+    /// Has no effect if the variant has no interrupt lines.
+    ///
+    /// This can only change the line between instructions. To raise an NMI partway
+    /// through a BRK or IRQ sequence, drive the line from the bus with [`Bus::nmi_line`].
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if !self.variant.has_interrupt_lines() {
+            return;
+        }
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
     }
 
-    pub fn reset(&mut self, bus: &mut impl Bus) {
-        CpuWithBus { cpu: self, bus }.reset()
+    pub fn nmi_line(&self) -> bool {
+        self.nmi_line
     }
 
-    pub fn irq(&mut self, bus: &mut impl Bus) {
-        CpuWithBus { cpu: self, bus }.irq()
+    /// Immediately run the IRQ sequence if interrupts are enabled and the CPU isn't halted,
+    /// returning the number of cycles it took. This bypasses the IRQ line.
+    pub fn irq(&mut self, bus: &mut impl Bus) -> u32 {
+        self.count_cycles(|cpu| CpuWithBus::new(cpu, bus).irq())
     }
 
-    pub fn nmi(&mut self, bus: &mut impl Bus) {
-        CpuWithBus { cpu: self, bus }.nmi()
+    /// Immediately run the NMI sequence if the CPU isn't halted, returning the number
+    /// of cycles it took. This bypasses the NMI line.
+    pub fn nmi(&mut self, bus: &mut impl Bus) -> u32 {
+        self.count_cycles(|cpu| CpuWithBus::new(cpu, bus).nmi())
+    }
+
+    fn count_cycles(&mut self, f: impl FnOnce(&mut Self)) -> u32 {
+        let start = self.cycles;
+        f(self);
+        (self.cycles - start) as u32
     }
 }
 
+// Generates the 65C02's RMB, SMB, BBR and BBS ops for each bit.
+macro_rules! bit_ops {
+    ($($bit:literal $rmb:ident $smb:ident $bbr:ident $bbs:ident)*) => {$(
+        fn $rmb(&mut self, addr: u16) {
+            self.modify_operand(addr, |_, n| n & !(1 << $bit));
+        }
+
+        fn $smb(&mut self, addr: u16) {
+            self.modify_operand(addr, |_, n| n | (1 << $bit));
+        }
+
+        fn $bbr(&mut self, addr: u16) {
+            self.branch_on_bit(addr, $bit, false);
+        }
+
+        fn $bbs(&mut self, addr: u16) {
+            self.branch_on_bit(addr, $bit, true);
+        }
+    )*};
+}
+
 struct CpuWithBus<'c, B> {
     cpu: &'c mut Cpu6502,
     bus: &'c mut B,
+    // The address an indexed addressing mode first produces, before the
+    // carry into the high byte has been applied. Consumed by the operand
+    // accessors to perform the extra cycle real hardware spends fixing it up.
+    uncorrected_addr: Option<u16>,
+    // The address of an immediate operand, which handlers read like any other operand
+    // even though it's part of the instruction.
+    immediate_addr: Option<u16>,
 }
 
-impl<B: Bus> CpuWithBus<'_, B> {
+impl<'c, B: Bus> CpuWithBus<'c, B> {
+    fn new(cpu: &'c mut Cpu6502, bus: &'c mut B) -> Self {
+        Self { cpu, bus, uncorrected_addr: None, immediate_addr: None }
+    }
+
+    fn cmos(&self) -> bool {
+        self.cpu.variant == CpuVariant::Wdc65C02
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.cpu.reg.decimal && self.cpu.variant.has_decimal_mode()
+    }
+
+    // Every cycle of the 6502 is exactly one bus access,
+    // so all accesses go through these to count cycles.
+    fn read(&mut self, addr: u16) -> u8 {
+        let kind = if self.immediate_addr == Some(addr) {
+            ReadKind::Operand
+        } else {
+            ReadKind::Data
+        };
+        self.read_as(addr, kind)
+    }
+
+    fn read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        self.cpu.cycles += 1;
+        self.bus.read_as(addr & self.cpu.variant.address_mask(), kind)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.cpu.cycles += 1;
+        self.bus.write(addr & self.cpu.variant.address_mask(), value);
+    }
+
+    // Accesses whose results are thrown away by the hardware.
+    // They always take a cycle, but are only forwarded to the
+    // bus when the CPU is set to be cycle accurate.
+    fn dummy_read(&mut self, addr: u16) {
+        self.cpu.cycles += 1;
+        if self.cpu.cycle_accurate {
+            self.bus.read_as(addr & self.cpu.variant.address_mask(), ReadKind::Dummy);
+        }
+    }
+
+    fn dummy_write(&mut self, addr: u16, value: u8) {
+        self.cpu.cycles += 1;
+        if self.cpu.cycle_accurate {
+            self.bus.write(addr & self.cpu.variant.address_mask(), value);
+        }
+    }
+
     fn read_u16(&mut self, high: u8, low: u8) -> u16 {
         let u16_low = u16::from_le_bytes([low, high]);
         let u16_high = u16::from_le_bytes([low.wrapping_add(1), high]);
-        u16::from_le_bytes([self.bus.read(u16_low), self.bus.read(u16_high)])
+        u16::from_le_bytes([self.read(u16_low), self.read(u16_high)])
+    }
+
+    // Reads across page boundaries, unlike `read_u16`.
+    fn read_u16_at(&mut self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.read(addr), self.read(addr.wrapping_add(1))])
     }
 
     fn take_u8_at_pc(&mut self) -> u8 {
-        let byte = self.bus.read(self.cpu.pc);
+        let byte = self.read_as(self.cpu.pc, ReadKind::Operand);
         self.cpu.pc = self.cpu.pc.wrapping_add(1);
         byte
     }
 
+    fn take_opcode(&mut self) -> u8 {
+        let opcode = self.read_as(self.cpu.pc, ReadKind::Opcode);
+        self.cpu.pc = self.cpu.pc.wrapping_add(1);
+        opcode
+    }
+
     fn take_u16_at_pc(&mut self) -> u16 {
         u16::from_le_bytes([self.take_u8_at_pc(), self.take_u8_at_pc()])
     }
 
+    fn stack_addr(&self) -> u16 {
+        u16::from_le_bytes([self.cpu.sp, STACK_BASE])
+    }
+
     fn stack_push(&mut self, value: u8) {
-        let addr = u16::from_le_bytes([self.cpu.sp, STACK_BASE]);
-        self.bus.write(addr, value);
+        self.write(self.stack_addr(), value);
         self.cpu.sp = self.cpu.sp.wrapping_sub(1);
     }
 
     fn stack_pop(&mut self) -> u8 {
         self.cpu.sp = self.cpu.sp.wrapping_add(1);
-        let addr = u16::from_le_bytes([self.cpu.sp, STACK_BASE]);
-        self.bus.read(addr)
+        self.read(self.stack_addr())
+    }
+
+    fn indexed_addr(&mut self, base: u16, index: u8) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        self.uncorrected_addr = Some((base & 0xFF00) | (addr & 0x00FF));
+        addr
+    }
+
+    // The cycle spent fixing up an indexed address. The NMOS 6502 reads the uncorrected
+    // address, while the 65C02 avoids a stray read by rereading the last byte of the instruction.
+    fn fix_up_addr(&mut self, uncorrected: u16) {
+        let addr = if self.cmos() { self.cpu.pc.wrapping_sub(1) } else { uncorrected };
+        self.dummy_read(addr);
+    }
+
+    // Indexed reads only spend a cycle on the uncorrected address if it was wrong.
+    fn read_operand(&mut self, addr: u16) -> u8 {
+        if let Some(uncorrected) = self.uncorrected_addr.take() {
+            if uncorrected != addr {
+                self.fix_up_addr(uncorrected);
+            }
+        }
+        self.read(addr)
+    }
+
+    // Indexed writes always spend a cycle on the uncorrected address,
+    // since they can't undo a write to the wrong one.
+    fn write_operand(&mut self, addr: u16, value: u8) {
+        if let Some(uncorrected) = self.uncorrected_addr.take() {
+            self.fix_up_addr(uncorrected);
+        }
+        self.write(addr, value);
+    }
+
+    // The NMOS 6502 writes the old value back while modifying it, where the 65C02 reads it again.
+    fn modify_operand(&mut self, addr: u16, op: impl FnOnce(&mut Self, u8) -> u8) -> u8 {
+        if let Some(uncorrected) = self.uncorrected_addr.take() {
+            self.fix_up_addr(uncorrected);
+        }
+        let n = self.read(addr);
+        if self.cmos() {
+            self.dummy_read(addr);
+        } else {
+            self.dummy_write(addr, n);
+        }
+        let result = op(self, n);
+        self.write(addr, result);
+        result
+    }
+
+    // The 65C02 doesn't spend a cycle fixing up indexed shifts that don't cross a page.
+    fn shift_operand(&mut self, addr: u16, op: impl FnOnce(&mut Self, u8) -> u8) -> u8 {
+        if self.cmos() && self.uncorrected_addr == Some(addr) {
+            self.uncorrected_addr = None;
+        }
+        self.modify_operand(addr, op)
+    }
+
+    // The unstable SHA/SHX/SHY/SHS stores AND the value with the high byte of the
+    // base address plus one. If indexing crosses a page, the value also replaces
+    // the high byte of the target address.
+    fn unstable_store(&mut self, addr: u16, value: u8) {
+        let uncorrected = self.uncorrected_addr.unwrap_or(addr);
+        let [addr_low, base_high] = uncorrected.to_le_bytes();
+        let value = value & base_high.wrapping_add(1);
+        let addr = if uncorrected != addr {
+            u16::from_le_bytes([addr_low, value])
+        } else {
+            addr
+        };
+        self.write_operand(addr, value);
     }
 
     fn interrupt(&mut self, vector: u8, brk: bool) {
         let [pc_low, pc_high] = self.cpu.pc.to_le_bytes();
         self.stack_push(pc_high);
         self.stack_push(pc_low);
+        // An NMI raised by now, the fourth cycle of the sequence, hijacks a BRK or IRQ
+        // sequence, which then jumps to the NMI handler instead. The status is still
+        // pushed as it would have been, so a hijacked BRK still has the B flag set.
+        self.sample_nmi();
         self.stack_push(self.cpu.reg.get_status(brk));
         self.cpu.reg.interrupt_disable = true;
+        if self.cmos() {
+            self.cpu.reg.decimal = false;
+        }
+        let vector = if std::mem::take(&mut self.cpu.nmi_pending) {
+            NMI_VECTOR
+        } else {
+            vector
+        };
         self.cpu.pc = self.read_u16(VECTOR_BASE, vector);
     }
 
+    fn hardware_interrupt(&mut self, vector: u8) {
+        // The opcode fetch is discarded, and the PC isn't incremented.
+        self.dummy_read(self.cpu.pc);
+        self.dummy_read(self.cpu.pc);
+        self.interrupt(vector, false);
+    }
+
     fn binary_adc(&mut self, operand: u8) {
         let operand = operand as u16;
         let carry = self.cpu.reg.carry as u16;
@@ -102,72 +529,86 @@ impl<B: Bus> CpuWithBus<'_, B> {
         self.cpu.reg.update_a(result as u8);
     }
 
+    fn branch(&mut self, condition: bool, addr: u16) {
+        if condition {
+            self.dummy_read(self.cpu.pc);
+            let [_, pc_high] = self.cpu.pc.to_le_bytes();
+            let [addr_low, addr_high] = addr.to_le_bytes();
+            if pc_high != addr_high {
+                self.dummy_read(u16::from_le_bytes([addr_low, pc_high]));
+            } else {
+                // Interrupts are polled before the extra cycle and not after it, so
+                // one that arrives during the branch waits for the next instruction.
+                self.cpu.skip_interrupt_poll = true;
+            }
+            self.cpu.pc = addr;
+        }
+    }
+
     fn reset(&mut self) {
+        self.cpu.nmi_pending = false;
+        self.cpu.interrupt_disable_at_poll = None;
+        self.cpu.skip_interrupt_poll = false;
+        self.cpu.waiting = false;
+        self.dummy_read(self.cpu.pc);
+        self.dummy_read(self.cpu.pc);
         self.cpu.reg.interrupt_disable = true;
+        if self.cmos() {
+            self.cpu.reg.decimal = false;
+        }
         // Apparently RESET also attempts save the CPU state
         // to the stack, but it's hijacked to do reads instead
-        // of writes. It still modifies sp, hence the subtraction.
-        self.cpu.sp = self.cpu.sp.wrapping_sub(3);
+        // of writes. It still modifies sp.
+        for _ in 0..3 {
+            self.dummy_read(self.stack_addr());
+            self.cpu.sp = self.cpu.sp.wrapping_sub(1);
+        }
         self.cpu.pc = self.read_u16(VECTOR_BASE, RESET_VECTOR);
     }
 
     fn irq(&mut self) {
-        if !self.cpu.reg.interrupt_disable {
-            self.interrupt(IRQ_BRK_VECTOR, false);
+        if !self.cpu.reg.interrupt_disable && self.cpu.halted.is_none() {
+            self.hardware_interrupt(IRQ_BRK_VECTOR);
         }
     }
 
     fn nmi(&mut self) {
-        self.interrupt(NMI_VECTOR, false);
+        if self.cpu.halted.is_none() {
+            self.hardware_interrupt(NMI_VECTOR);
+        }
     }
 
     // Branch ops
     fn bpl(&mut self, addr: u16) {
-        if !self.cpu.reg.negative {
-            self.cpu.pc = addr;
-        }
+        self.branch(!self.cpu.reg.negative, addr);
     }
     
     fn bmi(&mut self, addr: u16) {
-        if self.cpu.reg.negative {
-            self.cpu.pc = addr;
-        }
+        self.branch(self.cpu.reg.negative, addr);
     }
     
     fn bvc(&mut self, addr: u16) {
-        if !self.cpu.reg.overflow {
-            self.cpu.pc = addr;
-        }
+        self.branch(!self.cpu.reg.overflow, addr);
     }
     
     fn bvs(&mut self, addr: u16) {
-        if self.cpu.reg.overflow {
-            self.cpu.pc = addr;
-        }
+        self.branch(self.cpu.reg.overflow, addr);
     }
     
     fn bcc(&mut self, addr: u16) {
-        if !self.cpu.reg.carry {
-            self.cpu.pc = addr;
-        }
+        self.branch(!self.cpu.reg.carry, addr);
     }
     
     fn bcs(&mut self, addr: u16) {
-        if self.cpu.reg.carry {
-            self.cpu.pc = addr;
-        }
+        self.branch(self.cpu.reg.carry, addr);
     }
     
     fn bne(&mut self, addr: u16) {
-        if !self.cpu.reg.zero {
-            self.cpu.pc = addr;
-        }
+        self.branch(!self.cpu.reg.zero, addr);
     }
     
     fn beq(&mut self, addr: u16) {
-        if self.cpu.reg.zero {
-            self.cpu.pc = addr;
-        }
+        self.branch(self.cpu.reg.zero, addr);
     }
 
     // Flag ops
@@ -180,10 +621,12 @@ impl<B: Bus> CpuWithBus<'_, B> {
     }
     
     fn cli_implied(&mut self) {
+        self.cpu.interrupt_disable_at_poll = Some(self.cpu.reg.interrupt_disable);
         self.cpu.reg.interrupt_disable = false;
     }
     
     fn sei_implied(&mut self) {
+        self.cpu.interrupt_disable_at_poll = Some(self.cpu.reg.interrupt_disable);
         self.cpu.reg.interrupt_disable = true;
     }
     
@@ -204,18 +647,24 @@ impl<B: Bus> CpuWithBus<'_, B> {
         self.cpu.pc = addr;
     }
 
-    fn jsr(&mut self, addr: u16) {
-        let return_addr = self.cpu.pc.wrapping_sub(1);
-        let [ret_low, ret_high] = return_addr.to_le_bytes();
+    // JSR only fetches the high byte of its target after pushing the
+    // return address, so it does its own operand fetching.
+    fn jsr(&mut self) {
+        let addr_low = self.take_u8_at_pc();
+        self.dummy_read(self.stack_addr());
+        let [ret_low, ret_high] = self.cpu.pc.to_le_bytes();
         self.stack_push(ret_high);
         self.stack_push(ret_low);
-        self.cpu.pc = addr;
+        let addr_high = self.read_as(self.cpu.pc, ReadKind::Operand);
+        self.cpu.pc = u16::from_le_bytes([addr_low, addr_high]);
     }
 
     fn rts_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
         let ret_low = self.stack_pop();
         let ret_high = self.stack_pop();
         let return_addr = u16::from_le_bytes([ret_low, ret_high]);
+        self.dummy_read(return_addr);
         self.cpu.pc = return_addr.wrapping_add(1);
     }
 
@@ -226,6 +675,7 @@ impl<B: Bus> CpuWithBus<'_, B> {
     }
 
     fn rti_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
         let status = self.stack_pop();
         self.cpu.reg.set_status(status);
         let ret_low = self.stack_pop();
@@ -239,7 +689,9 @@ impl<B: Bus> CpuWithBus<'_, B> {
     }
     
     fn plp_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
         let status = self.stack_pop();
+        self.cpu.interrupt_disable_at_poll = Some(self.cpu.reg.interrupt_disable);
         self.cpu.reg.set_status(status);
     }
 
@@ -248,35 +700,36 @@ impl<B: Bus> CpuWithBus<'_, B> {
     }
 
     fn pla_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
         let a = self.stack_pop();
         self.cpu.reg.update_a(a);
     }
 
     // Store and load ops
     fn sty(&mut self, addr: u16) {
-        self.bus.write(addr, self.cpu.reg.y);
+        self.write_operand(addr, self.cpu.reg.y);
     }
 
     fn ldy(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_y(n);
     }
 
     fn stx(&mut self, addr: u16) {
-        self.bus.write(addr, self.cpu.reg.x);
+        self.write_operand(addr, self.cpu.reg.x);
     }
 
     fn ldx(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_x(n);
     }
 
     fn sta(&mut self, addr: u16) {
-        self.bus.write(addr, self.cpu.reg.a);
+        self.write_operand(addr, self.cpu.reg.a);
     }
 
     fn lda(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_a(n);
     }
 
@@ -322,279 +775,520 @@ impl<B: Bus> CpuWithBus<'_, B> {
         self.cpu.reg.update_x(self.cpu.reg.x.wrapping_sub(1))
     }
 
-    fn inc(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn inc_value(&mut self, n: u8) -> u8 {
         let result = n.wrapping_add(1);
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
     }
 
-    fn dec(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn inc(&mut self, addr: u16) {
+        self.modify_operand(addr, Self::inc_value);
+    }
+
+    fn dec_value(&mut self, n: u8) -> u8 {
         let result = n.wrapping_sub(1);
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
+    }
+
+    fn dec(&mut self, addr: u16) {
+        self.modify_operand(addr, Self::dec_value);
     }
 
     // Compare ops
+    fn compare(&mut self, register: u8, n: u8) {
+        self.cpu.reg.update_nz_flags(register.wrapping_sub(n));
+        self.cpu.reg.carry = register >= n;
+    }
+
     fn cpy(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
-        self.cpu.reg.update_nz_flags(self.cpu.reg.y.wrapping_sub(n));
-        self.cpu.reg.carry = self.cpu.reg.y >= n;
+        let n = self.read_operand(addr);
+        self.compare(self.cpu.reg.y, n);
     }
 
     fn cpx(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
-        self.cpu.reg.update_nz_flags(self.cpu.reg.x.wrapping_sub(n));
-        self.cpu.reg.carry = self.cpu.reg.x >= n;
+        let n = self.read_operand(addr);
+        self.compare(self.cpu.reg.x, n);
     }
 
     fn cmp(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
-        self.cpu.reg.update_nz_flags(self.cpu.reg.a.wrapping_sub(n));
-        self.cpu.reg.carry = self.cpu.reg.a >= n;
+        let n = self.read_operand(addr);
+        self.compare(self.cpu.reg.a, n);
     }
 
     // Math ops
-    fn adc(&mut self, addr: u16) {
-        let operand = self.bus.read(addr);
-        if !self.cpu.reg.decimal || self.cpu.decimal_mode_disabled {
+    fn adc_value(&mut self, operand: u8) {
+        if !self.decimal_mode() {
             self.binary_adc(operand);
         } else {
-            let mut carry_out = false;
-            let mut lower = (self.cpu.reg.a & 0xF) + (operand & 0xF) + self.cpu.reg.carry as u8;
-            let mut upper = (self.cpu.reg.a >> 4) + (operand >> 4);
-            if lower >= 10 {
-                lower = (lower - 10) & 0xF;
-                upper += 1;
-            }
-            if upper >= 10 {
-                upper = (upper - 10) & 0xF;
-                carry_out = true;
-            }
-            let result = (upper << 4) | lower;
-            self.cpu.reg.carry = carry_out;
-            self.cpu.reg.update_a(result);
-            //TODO set flags even in decimal mode
+            self.decimal_adc(operand);
         }
     }
 
-    fn sbc(&mut self, addr: u16) {
-        let operand = self.bus.read(addr);
-        if !self.cpu.reg.decimal || self.cpu.decimal_mode_disabled {
+    // The NMOS 6502 sets N and V from the sum before its upper digit is adjusted, and Z from
+    // the binary sum. The 65C02 sets N and Z from the decimal result instead. C is the same on
+    // both. See http://www.6502.org/tutorials/decimal_mode.html#A.
+    fn decimal_adc(&mut self, operand: u8) {
+        let binary_result = self.cpu.reg.a.wrapping_add(operand).wrapping_add(self.cpu.reg.carry as u8);
+        let a = self.cpu.reg.a as i16;
+        let operand = operand as i16;
+        let mut lower = (a & 0x0F) + (operand & 0x0F) + self.cpu.reg.carry as i16;
+        if lower >= 0x0A {
+            lower = ((lower + 0x06) & 0x0F) + 0x10;
+        }
+        let mut result = (a & 0xF0) + (operand & 0xF0) + lower;
+        let signed_result = (a & 0xF0) as u8 as i8 as i16 + (operand & 0xF0) as u8 as i8 as i16 + lower;
+        let negative = result & 0x80 != 0;
+        if result >= 0xA0 {
+            result += 0x60;
+        }
+        self.cpu.reg.carry = result >= 0x100;
+        self.cpu.reg.overflow = !(-128..=127).contains(&signed_result);
+        self.cpu.reg.update_a(result as u8);
+        if !self.cmos() {
+            self.cpu.reg.negative = negative;
+            self.cpu.reg.zero = binary_result == 0;
+        }
+    }
+
+    // The 65C02 also spends an extra cycle on decimal mode.
+    fn decimal_cycle(&mut self, addr: u16) {
+        if self.cmos() && self.decimal_mode() {
+            self.dummy_read(addr);
+        }
+    }
+
+    fn adc(&mut self, addr: u16) {
+        let operand = self.read_operand(addr);
+        self.decimal_cycle(addr);
+        self.adc_value(operand);
+    }
+
+    fn sbc_value(&mut self, operand: u8) {
+        if !self.decimal_mode() {
             self.binary_adc(!operand); // works due to two's complement
+        } else if self.cmos() {
+            self.cmos_decimal_sbc(operand);
         } else {
-            let mut carry_out = true;
-            let mut lower = (self.cpu.reg.a as i16 & 0xF) - (operand as i16 & 0xF) - !self.cpu.reg.carry as i16;
-            let mut upper = (self.cpu.reg.a as i16 >> 4) - (operand as i16 >> 4);
-            if lower & 0x10 != 0 {
-                lower = (lower + 10) & 0xF;
-                upper -= 1;
-            }
-            if upper & 0x10 != 0 {
-                upper = (upper + 10) & 0xF;
-                carry_out = false;
-            }
-            let result = (upper << 4) as u8 | lower as u8;
-            self.cpu.reg.carry = carry_out;
-            self.cpu.reg.update_a(result);
-            //TODO set flags even in decimal mode
+            self.nmos_decimal_sbc(operand);
         }
     }
 
+    // The NMOS 6502 sets all the flags as in binary mode.
+    // See http://www.6502.org/tutorials/decimal_mode.html#A.
+    fn nmos_decimal_sbc(&mut self, operand: u8) {
+        let a = self.cpu.reg.a as i16;
+        let borrow = !self.cpu.reg.carry as i16;
+        let mut lower = (a & 0x0F) - (operand as i16 & 0x0F) - borrow;
+        if lower < 0 {
+            lower = ((lower - 0x06) & 0x0F) - 0x10;
+        }
+        let mut result = (a & 0xF0) - (operand as i16 & 0xF0) + lower;
+        if result < 0 {
+            result -= 0x60;
+        }
+        self.binary_adc(!operand);
+        self.cpu.reg.a = result as u8;
+    }
+
+    fn cmos_decimal_sbc(&mut self, operand: u8) {
+        let a = self.cpu.reg.a as i16;
+        let borrow = !self.cpu.reg.carry as i16;
+        let lower = (a & 0x0F) - (operand as i16 & 0x0F) - borrow;
+        let mut result = a - operand as i16 - borrow;
+        if result < 0 {
+            result -= 0x60;
+        }
+        if lower < 0 {
+            result -= 0x06;
+        }
+        // C and V are the same as in binary mode.
+        self.binary_adc(!operand);
+        self.cpu.reg.update_a(result as u8);
+    }
+
+    fn sbc(&mut self, addr: u16) {
+        let operand = self.read_operand(addr);
+        self.decimal_cycle(addr);
+        self.sbc_value(operand);
+    }
+
     // Bitwise ops
     fn ora(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_a(self.cpu.reg.a | n);
     }
 
     fn and(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_a(self.cpu.reg.a & n);
     }
 
     fn eor(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.update_a(self.cpu.reg.a ^ n);
     }
 
     fn bit(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+        let n = self.read_operand(addr);
         self.cpu.reg.zero = self.cpu.reg.a & n == 0;
         self.cpu.reg.negative = n & 0b1000_0000 != 0;
         self.cpu.reg.overflow = n & 0b0100_0000 != 0;
     }
 
     // Bitwise read-modify-write ops
-    fn asl(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn asl_value(&mut self, n: u8) -> u8 {
         self.cpu.reg.carry = n & 0b1000_0000 != 0;
         let result = n << 1;
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
+    }
+
+    fn asl(&mut self, addr: u16) {
+        self.shift_operand(addr, Self::asl_value);
     }
 
     fn asl_implied(&mut self) {
-        self.cpu.reg.carry = self.cpu.reg.a & 0b1000_0000 != 0;
-        self.cpu.reg.update_a(self.cpu.reg.a << 1);
+        self.cpu.reg.a = self.asl_value(self.cpu.reg.a);
     }
 
-    fn rol(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn rol_value(&mut self, n: u8) -> u8 {
         let carry = self.cpu.reg.carry;
         self.cpu.reg.carry = n & 0b1000_0000 != 0;
         let result = (n << 1) | carry as u8;
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
+    }
+
+    fn rol(&mut self, addr: u16) {
+        self.shift_operand(addr, Self::rol_value);
     }
 
     fn rol_implied(&mut self) {
-        let carry = self.cpu.reg.carry;
-        self.cpu.reg.carry = self.cpu.reg.a & 0b1000_0000 != 0;
-        self.cpu.reg.update_a((self.cpu.reg.a << 1) | carry as u8);
+        self.cpu.reg.a = self.rol_value(self.cpu.reg.a);
     }
 
-    fn lsr(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn lsr_value(&mut self, n: u8) -> u8 {
         self.cpu.reg.carry = n & 0b0000_0001 != 0;
         let result = n >> 1;
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
+    }
+
+    fn lsr(&mut self, addr: u16) {
+        self.shift_operand(addr, Self::lsr_value);
     }
 
     fn lsr_implied(&mut self) {
-        self.cpu.reg.carry = self.cpu.reg.a & 0b0000_0001 != 0;
-        self.cpu.reg.update_a(self.cpu.reg.a >> 1);
+        self.cpu.reg.a = self.lsr_value(self.cpu.reg.a);
     }
 
-    fn ror(&mut self, addr: u16) {
-        let n = self.bus.read(addr);
+    fn ror_value(&mut self, n: u8) -> u8 {
         let carry = self.cpu.reg.carry;
         self.cpu.reg.carry = n & 0b0000_0001 != 0;
         let result = (n >> 1) | ((carry as u8) << 7);
-        self.bus.write(addr, result);
         self.cpu.reg.update_nz_flags(result);
+        result
+    }
+
+    fn ror(&mut self, addr: u16) {
+        self.shift_operand(addr, Self::ror_value);
     }
 
     fn ror_implied(&mut self) {
-        let carry = self.cpu.reg.carry;
-        self.cpu.reg.carry = self.cpu.reg.a & 0b0000_0001 != 0;
-        self.cpu.reg.update_a((self.cpu.reg.a >> 1) | ((carry as u8) << 7));
+        self.cpu.reg.a = self.ror_value(self.cpu.reg.a);
     }
 
     // No op
     fn nop_implied(&mut self) {
     }
 
+    // 65C02 ops
+    fn bra(&mut self, addr: u16) {
+        self.branch(true, addr);
+    }
+
+    fn phx_implied(&mut self) {
+        self.stack_push(self.cpu.reg.x);
+    }
+
+    fn plx_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
+        let x = self.stack_pop();
+        self.cpu.reg.update_x(x);
+    }
+
+    fn phy_implied(&mut self) {
+        self.stack_push(self.cpu.reg.y);
+    }
+
+    fn ply_implied(&mut self) {
+        self.dummy_read(self.stack_addr());
+        let y = self.stack_pop();
+        self.cpu.reg.update_y(y);
+    }
+
+    fn stz(&mut self, addr: u16) {
+        self.write_operand(addr, 0);
+    }
+
+    fn tsb(&mut self, addr: u16) {
+        self.modify_operand(addr, |this, n| {
+            this.cpu.reg.zero = this.cpu.reg.a & n == 0;
+            n | this.cpu.reg.a
+        });
+    }
+
+    fn trb(&mut self, addr: u16) {
+        self.modify_operand(addr, |this, n| {
+            this.cpu.reg.zero = this.cpu.reg.a & n == 0;
+            n & !this.cpu.reg.a
+        });
+    }
+
+    // Unlike the other addressing modes, immediate BIT only sets Z.
+    fn bit_immediate(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        self.cpu.reg.zero = self.cpu.reg.a & n == 0;
+    }
+
+    fn inc_implied(&mut self) {
+        self.cpu.reg.a = self.inc_value(self.cpu.reg.a);
+    }
+
+    fn dec_implied(&mut self) {
+        self.cpu.reg.a = self.dec_value(self.cpu.reg.a);
+    }
+
+    // BBR and BBS read the zero page value before fetching the branch offset.
+    fn branch_on_bit(&mut self, addr: u16, bit: u8, set: bool) {
+        let n = self.read(addr);
+        self.dummy_read(addr);
+        let offset = self.take_u8_at_pc() as i8 as u16;
+        let target = self.cpu.pc.wrapping_add(offset);
+        self.branch((n & (1 << bit) != 0) == set, target);
+    }
+
+    bit_ops! {
+        0 rmb0 smb0 bbr0 bbs0
+        1 rmb1 smb1 bbr1 bbs1
+        2 rmb2 smb2 bbr2 bbs2
+        3 rmb3 smb3 bbr3 bbs3
+        4 rmb4 smb4 bbr4 bbs4
+        5 rmb5 smb5 bbr5 bbs5
+        6 rmb6 smb6 bbr6 bbs6
+        7 rmb7 smb7 bbr7 bbs7
+    }
+
+    fn wai_implied(&mut self) {
+        self.dummy_read(self.cpu.pc);
+        self.cpu.waiting = true;
+    }
+
+    // The 65C02's undefined opcode $5C reads from $FFxx, then spends another four cycles.
+    fn nop_long(&mut self, addr: u16) {
+        let addr = 0xFF00 | (addr & 0x00FF);
+        for _ in 0..5 {
+            self.dummy_read(addr);
+        }
+    }
+
     // Illegal opcodes
     fn stp_implied(&mut self) {
-        //TODO impl
+        // The PC is left on the opcode, as the CPU never gets any further.
+        self.cpu.pc = self.cpu.pc.wrapping_sub(1);
+        self.cpu.halted = Some(CpuHalted { pc: self.cpu.pc });
     }
     
     fn slo(&mut self, addr: u16) {
-        self.asl(addr);
-        self.ora(addr);
+        let n = self.modify_operand(addr, Self::asl_value);
+        self.cpu.reg.update_a(self.cpu.reg.a | n);
     }
     
-    fn nop(&mut self, _addr: u16) {
-        //TODO impl
+    fn nop(&mut self, addr: u16) {
+        self.read_operand(addr);
     }
     
-    fn anc(&mut self, _addr: u16) {
-        //TODO impl
+    fn anc(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        self.cpu.reg.update_a(self.cpu.reg.a & n);
+        self.cpu.reg.carry = self.cpu.reg.negative;
     }
 
     fn rla(&mut self, addr: u16) {
-        self.rol(addr);
-        self.and(addr);
+        let n = self.modify_operand(addr, Self::rol_value);
+        self.cpu.reg.update_a(self.cpu.reg.a & n);
     }
     
     fn sre(&mut self, addr: u16) {
-        self.lsr(addr);
-        self.eor(addr);
+        let n = self.modify_operand(addr, Self::lsr_value);
+        self.cpu.reg.update_a(self.cpu.reg.a ^ n);
     }
     
-    fn alr(&mut self, _addr: u16) {
-        //TODO impl
+    fn alr(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        self.cpu.reg.a = self.lsr_value(self.cpu.reg.a & n);
     }
     
     fn rra(&mut self, addr: u16) {
-        self.ror(addr);
-        self.adc(addr);
+        let n = self.modify_operand(addr, Self::ror_value);
+        self.adc_value(n);
     }
     
-    fn arr(&mut self, _addr: u16) {
-        //TODO impl
+    fn arr(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        let and = self.cpu.reg.a & n;
+        let result = (and >> 1) | ((self.cpu.reg.carry as u8) << 7);
+        self.cpu.reg.update_a(result);
+        if !self.decimal_mode() {
+            self.cpu.reg.carry = result & 0b0100_0000 != 0;
+            self.cpu.reg.overflow = ((result >> 6) ^ (result >> 5)) & 1 != 0;
+        } else {
+            // N and Z reflect the binary result, but A and C get a BCD fixup.
+            self.cpu.reg.overflow = (and ^ result) & 0b0100_0000 != 0;
+            let mut result = result;
+            if (and & 0x0F) + (and & 0x01) > 0x05 {
+                result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+            }
+            self.cpu.reg.carry = (and as u16 & 0xF0) + (and as u16 & 0x10) > 0x50;
+            if self.cpu.reg.carry {
+                result = result.wrapping_add(0x60);
+            }
+            self.cpu.reg.a = result;
+        }
     }
     
     fn sax(&mut self, addr: u16) {
-        self.bus.write(addr, self.cpu.reg.a & self.cpu.reg.x);
+        self.write_operand(addr, self.cpu.reg.a & self.cpu.reg.x);
     }
     
-    fn xaa(&mut self, _addr: u16) {
-        //TODO impl
+    fn xaa(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        let a = (self.cpu.reg.a | self.cpu.unstable_magic) & self.cpu.reg.x & n;
+        self.cpu.reg.update_a(a);
     }
     
-    fn ahx(&mut self, _addr: u16) {
-        //TODO impl
+    fn ahx(&mut self, addr: u16) {
+        self.unstable_store(addr, self.cpu.reg.a & self.cpu.reg.x);
     }
     
-    fn tas(&mut self, _addr: u16) {
-        //TODO impl
+    fn tas(&mut self, addr: u16) {
+        self.cpu.sp = self.cpu.reg.a & self.cpu.reg.x;
+        self.unstable_store(addr, self.cpu.sp);
     }
     
-    fn shy(&mut self, _addr: u16) {
-        //TODO impl
+    fn shy(&mut self, addr: u16) {
+        self.unstable_store(addr, self.cpu.reg.y);
     }
     
-    fn shx(&mut self, _addr: u16) {
-        //TODO impl
+    fn shx(&mut self, addr: u16) {
+        self.unstable_store(addr, self.cpu.reg.x);
     }
     
     fn lax(&mut self, addr: u16) {
-        self.lda(addr);
-        self.tax_implied();
+        let n = self.read_operand(addr);
+        self.cpu.reg.update_a(n);
+        self.cpu.reg.update_x(n);
     }
     
-    fn las(&mut self, _addr: u16) {
-        //TODO impl
+    fn lxa(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        let a = (self.cpu.reg.a | self.cpu.unstable_magic) & n;
+        self.cpu.reg.update_a(a);
+        self.cpu.reg.update_x(a);
+    }
+    
+    fn las(&mut self, addr: u16) {
+        let n = self.read_operand(addr) & self.cpu.sp;
+        self.cpu.sp = n;
+        self.cpu.reg.update_a(n);
+        self.cpu.reg.update_x(n);
     }
     
     fn dcp(&mut self, addr: u16) {
-        self.dec(addr);
-        self.cmp(addr);
+        let n = self.modify_operand(addr, Self::dec_value);
+        self.compare(self.cpu.reg.a, n);
     }
     
-    fn axs(&mut self, _addr: u16) {
-        //TODO impl
+    fn axs(&mut self, addr: u16) {
+        let n = self.read_operand(addr);
+        let and = self.cpu.reg.a & self.cpu.reg.x;
+        self.cpu.reg.carry = and >= n;
+        self.cpu.reg.update_x(and.wrapping_sub(n));
     }
     
-    fn isc(&mut self, addr: u16) {
-        self.inc(addr);
-        self.sbc(addr);
+    fn isb(&mut self, addr: u16) {
+        let n = self.modify_operand(addr, Self::inc_value);
+        self.sbc_value(n);
+    }
+
+    // Update the NMI line from the bus, if the bus drives it.
+    fn sample_nmi(&mut self) {
+        if let Some(asserted) = self.bus.nmi_line() {
+            self.cpu.set_nmi(asserted);
+        }
+    }
+
+    // The hardware polls the interrupt lines near the end of each instruction. The lines
+    // only change between steps, so the poll is done at the start of the next step instead.
+    fn poll_interrupts(&mut self) -> Option<u8> {
+        let interrupt_disable = self.cpu.interrupt_disable_at_poll.take()
+            .unwrap_or(self.cpu.reg.interrupt_disable);
+        if std::mem::take(&mut self.cpu.skip_interrupt_poll) {
+            return None;
+        }
+        if self.cpu.nmi_pending {
+            self.cpu.nmi_pending = false;
+            Some(NMI_VECTOR)
+        } else if self.cpu.irq_sources != 0 && !interrupt_disable {
+            Some(IRQ_BRK_VECTOR)
+        } else {
+            None
+        }
     }
 
     fn step(&mut self) {
-        // #i    - immediate value
-        // d     - zero page address
-        // *+d   - relative address
-        // a     - absolute address
-        // ($a)  - dereference $a
-        // $l,$r - add $l and $r
+        self.uncorrected_addr = None;
+        self.immediate_addr = None;
+        self.sample_nmi();
+        if self.cpu.waiting {
+            if !self.cpu.nmi_pending && self.cpu.irq_sources == 0 {
+                self.dummy_read(self.cpu.pc);
+                return;
+            }
+            // Resumes even if IRQs are disabled, continuing after the WAI without handling the IRQ.
+            self.cpu.waiting = false;
+        }
+        if let Some(vector) = self.poll_interrupts() {
+            self.hardware_interrupt(vector);
+            // The first instruction of the handler always runs.
+            self.cpu.skip_interrupt_poll = true;
+            return;
+        }
+
         macro_rules! dispatch {
-            ($($opcode:literal $handler:ident($($addr_mode:tt)*))*) => {
-                match self.take_u8_at_pc() {
+            ($($opcode:literal $handler:ident($($addr_mode:tt)*) $($illegal:ident)?)*) => {
+                match self.take_opcode() {
                     $($opcode => dispatch!(@call $handler $($addr_mode)*),)*
                 }
             };
 
+            // The 65C02's single cycle NOPs don't even read the next byte.
+            (@call nop_1cycle) => {{}};
+
             (@call $handler:ident) => {{
+                self.dummy_read(self.cpu.pc);
                 self.$handler();
             }};
 
+            (@call jsr "a") => {{
+                self.jsr();
+            }};
+
             (@call $handler:ident "#i") => {{
                 let addr = self.cpu.pc;
-                self.take_u8_at_pc();
+                self.immediate_addr = Some(addr);
+                self.cpu.pc = self.cpu.pc.wrapping_add(1);
                 self.$handler(addr);
             }};
             
@@ -612,7 +1306,31 @@ impl<B: Bus> CpuWithBus<'_, B> {
             (@call $handler:ident "(a)") => {{
                 let low = self.take_u8_at_pc();
                 let high = self.take_u8_at_pc();
-                let addr = self.read_u16(high, low);
+                let addr = if self.cmos() {
+                    // The 65C02 spends an extra cycle carrying into the high byte of the pointer.
+                    self.dummy_read(self.cpu.pc.wrapping_sub(1));
+                    self.read_u16_at(u16::from_le_bytes([low, high]))
+                } else {
+                    self.read_u16(high, low)
+                };
+                self.$handler(addr);
+            }};
+
+            (@call $handler:ident "(a,x)") => {{
+                let base = self.take_u16_at_pc();
+                self.dummy_read(self.cpu.pc.wrapping_sub(1));
+                let addr = self.read_u16_at(base.wrapping_add(self.cpu.reg.x as u16));
+                self.$handler(addr);
+            }};
+
+            (@call $handler:ident "(d)") => {{
+                let addr = self.take_u8_at_pc();
+                let addr = self.read_u16(0, addr);
+                self.$handler(addr);
+            }};
+
+            (@call $handler:ident "d,*+d") => {{
+                let addr = self.take_u8_at_pc() as u16;
                 self.$handler(addr);
             }};
             
@@ -622,295 +1340,50 @@ impl<B: Bus> CpuWithBus<'_, B> {
             }};
             
             (@call $handler:ident "a,x") => {{
-                let addr = self.take_u16_at_pc().wrapping_add(self.cpu.reg.x as u16);
+                let base = self.take_u16_at_pc();
+                let addr = self.indexed_addr(base, self.cpu.reg.x);
                 self.$handler(addr);
             }};
             
             (@call $handler:ident "a,y") => {{
-                let addr = self.take_u16_at_pc().wrapping_add(self.cpu.reg.y as u16);
+                let base = self.take_u16_at_pc();
+                let addr = self.indexed_addr(base, self.cpu.reg.y);
                 self.$handler(addr);
             }};
             
             (@call $handler:ident "d,x") => {{
-                let addr = self.take_u8_at_pc().wrapping_add(self.cpu.reg.x) as u16;
+                let base = self.take_u8_at_pc();
+                self.dummy_read(base as u16);
+                let addr = base.wrapping_add(self.cpu.reg.x) as u16;
                 self.$handler(addr);
             }};
             
             (@call $handler:ident "d,y") => {{
-                let addr = self.take_u8_at_pc().wrapping_add(self.cpu.reg.y) as u16;
+                let base = self.take_u8_at_pc();
+                self.dummy_read(base as u16);
+                let addr = base.wrapping_add(self.cpu.reg.y) as u16;
                 self.$handler(addr);
             }};
             
             (@call $handler:ident "(d,x)") => {{
-                let addr = self.take_u8_at_pc().wrapping_add(self.cpu.reg.x);
+                let base = self.take_u8_at_pc();
+                self.dummy_read(base as u16);
+                let addr = base.wrapping_add(self.cpu.reg.x);
                 let addr = self.read_u16(0, addr);
                 self.$handler(addr);
             }};
             
             (@call $handler:ident "(d),y") => {{
                 let addr = self.take_u8_at_pc();
-                let addr = self.read_u16(0, addr).wrapping_add(self.cpu.reg.y as u16);
+                let base = self.read_u16(0, addr);
+                let addr = self.indexed_addr(base, self.cpu.reg.y);
                 self.$handler(addr);
             }};
         }
 
-        dispatch! {
-            0x00 brk_implied()
-            0x01 ora("(d,x)")
-            0x02 stp_implied() // illegal
-            0x03 slo("(d,x)") // illegal
-            0x04 nop("d") // illegal
-            0x05 ora("d")
-            0x06 asl("d")
-            0x07 slo("d") // illegal
-            0x08 php_implied()
-            0x09 ora("#i")
-            0x0A asl_implied()
-            0x0B anc("#i") // illegal
-            0x0C nop("a") // illegal
-            0x0D ora("a")
-            0x0E asl("a")
-            0x0F slo("a") // illegal
-            0x10 bpl("*+d")
-            0x11 ora("(d),y")
-            0x12 stp_implied() // illegal
-            0x13 slo("(d),y") // illegal
-            0x14 nop("d,x") // illegal
-            0x15 ora("d,x")
-            0x16 asl("d,x")
-            0x17 slo("d,x") // illegal
-            0x18 clc_implied()
-            0x19 ora("a,y")
-            0x1A nop_implied() // illegal
-            0x1B slo("a,y") // illegal
-            0x1C nop("a,x") // illegal
-            0x1D ora("a,x")
-            0x1E asl("a,x")
-            0x1F slo("a,x") // illegal
-            0x20 jsr("a")
-            0x21 and("(d,x)")
-            0x22 stp_implied() // illegal
-            0x23 rla("(d,x)") // illegal
-            0x24 bit("d")
-            0x25 and("d")
-            0x26 rol("d")
-            0x27 rla("d") // illegal
-            0x28 plp_implied()
-            0x29 and("#i")
-            0x2A rol_implied()
-            0x2B anc("#i") // illegal
-            0x2C bit("a")
-            0x2D and("a")
-            0x2E rol("a")
-            0x2F rla("a") // illegal
-            0x30 bmi("*+d")
-            0x31 and("(d),y")
-            0x32 stp_implied() // illegal
-            0x33 rla("(d),y") // illegal
-            0x34 nop("d,x") // illegal
-            0x35 and("d,x")
-            0x36 rol("d,x")
-            0x37 rla("d,x") // illegal
-            0x38 sec_implied()
-            0x39 and("a,y")
-            0x3A nop_implied() // illegal
-            0x3B rla("a,y") // illegal
-            0x3C nop("a,x") // illegal
-            0x3D and("a,x")
-            0x3E rol("a,x")
-            0x3F rla("a,x") // illegal
-            0x40 rti_implied()
-            0x41 eor("(d,x)")
-            0x42 stp_implied() // illegal
-            0x43 sre("(d,x)") // illegal
-            0x44 nop("d") // illegal
-            0x45 eor("d")
-            0x46 lsr("d")
-            0x47 sre("d") // illegal
-            0x48 pha_implied()
-            0x49 eor("#i")
-            0x4A lsr_implied()
-            0x4B alr("#i") // illegal
-            0x4C jmp("a")
-            0x4D eor("a")
-            0x4E lsr("a")
-            0x4F sre("a") // illegal
-            0x50 bvc("*+d")
-            0x51 eor("(d),y")
-            0x52 stp_implied() // illegal
-            0x53 sre("(d),y") // illegal
-            0x54 nop("d,x") // illegal
-            0x55 eor("d,x")
-            0x56 lsr("d,x")
-            0x57 sre("d,x") // illegal
-            0x58 cli_implied()
-            0x59 eor("a,y")
-            0x5A nop_implied() // illegal
-            0x5B sre("a,y") // illegal
-            0x5C nop("a,x") // illegal
-            0x5D eor("a,x")
-            0x5E lsr("a,x")
-            0x5F sre("a,x") // illegal
-            0x60 rts_implied()
-            0x61 adc("(d,x)")
-            0x62 stp_implied() // illegal
-            0x63 rra("(d,x)") // illegal
-            0x64 nop("d") // illegal
-            0x65 adc("d")
-            0x66 ror("d")
-            0x67 rra("d") // illegal
-            0x68 pla_implied()
-            0x69 adc("#i")
-            0x6A ror_implied()
-            0x6B arr("#i") // illegal
-            0x6C jmp("(a)")
-            0x6D adc("a")
-            0x6E ror("a")
-            0x6F rra("a") // illegal
-            0x70 bvs("*+d")
-            0x71 adc("(d),y")
-            0x72 stp_implied() // illegal
-            0x73 rra("(d),y") // illegal
-            0x74 nop("d,x") // illegal
-            0x75 adc("d,x")
-            0x76 ror("d,x")
-            0x77 rra("d,x") // illegal
-            0x78 sei_implied()
-            0x79 adc("a,y")
-            0x7A nop_implied() // illegal
-            0x7B rra("a,y") // illegal
-            0x7C nop("a,x") // illegal
-            0x7D adc("a,x")
-            0x7E ror("a,x")
-            0x7F rra("a,x") // illegal
-            0x80 nop("#i") // illegal
-            0x81 sta("(d,x)")
-            0x82 nop("#i") // illegal
-            0x83 sax("(d,x)") // illegal
-            0x84 sty("d")
-            0x85 sta("d")
-            0x86 stx("d")
-            0x87 sax("d") // illegal
-            0x88 dey_implied()
-            0x89 nop("#i") // illegal
-            0x8A txa_implied()
-            0x8B xaa("#i") // illegal
-            0x8C sty("a")
-            0x8D sta("a")
-            0x8E stx("a")
-            0x8F sax("a") // illegal
-            0x90 bcc("*+d")
-            0x91 sta("(d),y")
-            0x92 stp_implied() // illegal
-            0x93 ahx("(d),y") // illegal
-            0x94 sty("d,x")
-            0x95 sta("d,x")
-            0x96 stx("d,y")
-            0x97 sax("d,y") // illegal
-            0x98 tya_implied()
-            0x99 sta("a,y")
-            0x9A txs_implied()
-            0x9B tas("a,y") // illegal
-            0x9C shy("a,x") // illegal
-            0x9D sta("a,x")
-            0x9E shx("a,y") // illegal
-            0x9F ahx("a,y") // illegal
-            0xA0 ldy("#i")
-            0xA1 lda("(d,x)")
-            0xA2 ldx("#i")
-            0xA3 lax("(d,x)") // illegal
-            0xA4 ldy("d")
-            0xA5 lda("d")
-            0xA6 ldx("d")
-            0xA7 lax("d") // illegal
-            0xA8 tay_implied()
-            0xA9 lda("#i")
-            0xAA tax_implied()
-            0xAB lax("#i") // illegal
-            0xAC ldy("a")
-            0xAD lda("a")
-            0xAE ldx("a")
-            0xAF lax("a") // illegal
-            0xB0 bcs("*+d")
-            0xB1 lda("(d),y")
-            0xB2 stp_implied() // illegal
-            0xB3 lax("(d),y") // illegal
-            0xB4 ldy("d,x")
-            0xB5 lda("d,x")
-            0xB6 ldx("d,y")
-            0xB7 lax("d,y") // illegal
-            0xB8 clv_implied()
-            0xB9 lda("a,y")
-            0xBA tsx_implied()
-            0xBB las("a,y") // illegal
-            0xBC ldy("a,x")
-            0xBD lda("a,x")
-            0xBE ldx("a,y")
-            0xBF lax("a,y") // illegal
-            0xC0 cpy("#i")
-            0xC1 cmp("(d,x)")
-            0xC2 nop("#i") // illegal
-            0xC3 dcp("(d,x)") // illegal
-            0xC4 cpy("d")
-            0xC5 cmp("d")
-            0xC6 dec("d")
-            0xC7 dcp("d") // illegal
-            0xC8 iny_implied()
-            0xC9 cmp("#i")
-            0xCA dex_implied()
-            0xCB axs("#i") // illegal
-            0xCC cpy("a")
-            0xCD cmp("a")
-            0xCE dec("a")
-            0xCF dcp("a") // illegal
-            0xD0 bne("*+d")
-            0xD1 cmp("(d),y")
-            0xD2 stp_implied() // illegal
-            0xD3 dcp("(d),y") // illegal
-            0xD4 nop("d,x") // illegal
-            0xD5 cmp("d,x")
-            0xD6 dec("d,x")
-            0xD7 dcp("d,x") // illegal
-            0xD8 cld_implied()
-            0xD9 cmp("a,y")
-            0xDA nop_implied() // illegal
-            0xDB dcp("a,y") // illegal
-            0xDC nop("a,x") // illegal
-            0xDD cmp("a,x")
-            0xDE dec("a,x")
-            0xDF dcp("a,x") // illegal
-            0xE0 cpx("#i")
-            0xE1 sbc("(d,x)")
-            0xE2 nop("#i") // illegal
-            0xE3 isc("(d,x)") // illegal
-            0xE4 cpx("d")
-            0xE5 sbc("d")
-            0xE6 inc("d")
-            0xE7 isc("d") // illegal
-            0xE8 inx_implied()
-            0xE9 sbc("#i")
-            0xEA nop_implied()
-            0xEB sbc("#i") // illegal
-            0xEC cpx("a")
-            0xED sbc("a")
-            0xEE inc("a")
-            0xEF isc("a") // illegal
-            0xF0 beq("*+d")
-            0xF1 sbc("(d),y")
-            0xF2 stp_implied() // illegal
-            0xF3 isc("(d),y") // illegal
-            0xF4 nop("d,x") // illegal
-            0xF5 sbc("d,x")
-            0xF6 inc("d,x")
-            0xF7 isc("d,x") // illegal
-            0xF8 sed_implied()
-            0xF9 sbc("a,y")
-            0xFA nop_implied() // illegal
-            0xFB isc("a,y") // illegal
-            0xFC nop("a,x") // illegal
-            0xFD sbc("a,x")
-            0xFE inc("a,x")
-            0xFF isc("a,x") // illegal
+        match self.cpu.variant {
+            CpuVariant::Wdc65C02 => cmos_opcodes!(dispatch),
+            _ => nmos_opcodes!(dispatch),
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use crate::cpu::{Bus, Cpu6502, CpuHalted, ReadKind};
+
+const JSR_OPCODE: u8 = 0x20;
+const RTS_OPCODE: u8 = 0x60;
+const RTI_OPCODE: u8 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Watches a range of addresses for accesses of the enabled kinds.
+/// Reads are only the CPU reading data, not fetching instructions or making dummy reads.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, addr: u16, kind: AccessKind) -> bool {
+        let enabled = match kind {
+            AccessKind::Read => self.read,
+            AccessKind::Write => self.write,
+            AccessKind::Execute => self.execute,
+        };
+        enabled && self.range.contains(&addr)
+    }
+}
+
+/// Why the debugger stopped running the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The CPU is about to execute an instruction at a breakpoint.
+    Breakpoint(u16),
+    /// A watchpoint was hit. Reads and writes stop after the instruction
+    /// accessing the address, while executes stop before the instruction.
+    Watchpoint { addr: u16, kind: AccessKind },
+    /// The requested step or run finished.
+    Done,
+    Halted(CpuHalted),
+}
+
+type Condition = Box<dyn Fn(&Cpu6502) -> bool>;
+
+/// Runs a [`Cpu6502`] on any [`Bus`], stopping at breakpoints and watchpoints.
+///
+/// Every run executes at least one instruction before checking breakpoints,
+/// so continuing after stopping at a breakpoint doesn't immediately stop again.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: HashMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr, None);
+    }
+
+    /// Add a breakpoint that only stops if `condition` holds for the CPU state at that point.
+    /// Replaces any existing breakpoint at `addr`.
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: impl Fn(&Cpu6502) -> bool + 'static) {
+        self.breakpoints.insert(addr, Some(Box::new(condition)));
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> StopReason {
+        self.run_until(cpu, bus, |_, _| true)
+    }
+
+    /// Execute a single instruction, or a whole subroutine if the instruction is a `JSR`.
+    pub fn step_over(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> StopReason {
+        let return_addr = cpu.pc.wrapping_add(3);
+        let sp = cpu.sp;
+        // Whether the first instruction was a `JSR`, going by the opcode the CPU fetched,
+        // since not every bus can peek.
+        let mut jsr = None;
+        self.run_until(cpu, bus, |cpu, opcode| {
+            let jsr = *jsr.get_or_insert(opcode == Some(JSR_OPCODE));
+            !jsr || (cpu.pc == return_addr && cpu.sp == sp)
+        })
+    }
+
+    /// Run until the current subroutine or interrupt handler returns.
+    pub fn step_out(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> StopReason {
+        let sp = cpu.sp;
+        self.run_until(cpu, bus, |cpu, opcode| {
+            // The stack pointer can wrap around as the return pops it.
+            let popped = cpu.sp.wrapping_sub(sp) as i8 > 0;
+            matches!(opcode, Some(RTS_OPCODE | RTI_OPCODE)) && popped
+        })
+    }
+
+    /// Run until the CPU's cycle counter reaches `cycle`.
+    pub fn run_until_cycle(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus, cycle: u64) -> StopReason {
+        if cpu.cycles >= cycle {
+            return StopReason::Done;
+        }
+        self.run_until(cpu, bus, |cpu, _| cpu.cycles >= cycle)
+    }
+
+    /// Run until a breakpoint or watchpoint is hit, or the CPU halts.
+    pub fn run(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> StopReason {
+        self.run_until(cpu, bus, |_, _| false)
+    }
+
+    // `done` is called after every instruction with the opcode that was executed.
+    fn run_until(
+        &mut self,
+        cpu: &mut Cpu6502,
+        bus: &mut impl Bus,
+        mut done: impl FnMut(&Cpu6502, Option<u8>) -> bool,
+    ) -> StopReason {
+        let mut first = true;
+        loop {
+            if !first {
+                if let Some(reason) = self.check_breakpoints(cpu) {
+                    return reason;
+                }
+            }
+            first = false;
+
+            let mut bus = WatchBus {
+                bus: &mut *bus,
+                watchpoints: &self.watchpoints,
+                opcode: None,
+                hit: None,
+            };
+            if let Err(halted) = cpu.step(&mut bus) {
+                return StopReason::Halted(halted);
+            }
+            if let Some(reason) = bus.hit {
+                return reason;
+            }
+            if done(cpu, bus.opcode) {
+                return StopReason::Done;
+            }
+        }
+    }
+
+    fn check_breakpoints(&self, cpu: &Cpu6502) -> Option<StopReason> {
+        if let Some(condition) = self.breakpoints.get(&cpu.pc) {
+            if condition.as_ref().is_none_or(|condition| condition(cpu)) {
+                return Some(StopReason::Breakpoint(cpu.pc));
+            }
+        }
+        let execute = self.watchpoints.iter().any(|w| w.matches(cpu.pc, AccessKind::Execute));
+        execute.then_some(StopReason::Watchpoint { addr: cpu.pc, kind: AccessKind::Execute })
+    }
+}
+
+struct WatchBus<'d, B> {
+    bus: &'d mut B,
+    watchpoints: &'d [Watchpoint],
+    opcode: Option<u8>,
+    hit: Option<StopReason>,
+}
+
+impl<B: Bus> WatchBus<'_, B> {
+    fn watch(&mut self, addr: u16, kind: AccessKind) {
+        if self.hit.is_none() && self.watchpoints.iter().any(|w| w.matches(addr, kind)) {
+            self.hit = Some(StopReason::Watchpoint { addr, kind });
+        }
+    }
+}
+
+impl<B: Bus> Bus for WatchBus<'_, B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.read_as(addr, ReadKind::Data)
+    }
+
+    fn read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        let value = self.bus.read_as(addr, kind);
+        match kind {
+            ReadKind::Opcode => self.opcode = Some(value),
+            // Fetching instructions is watched by `execute`, and dummy reads aren't used.
+            ReadKind::Data => self.watch(addr, AccessKind::Read),
+            ReadKind::Operand | ReadKind::Dummy => {}
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.bus.write(addr, value);
+        self.watch(addr, AccessKind::Write);
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.bus.peek(addr)
+    }
+
+    fn nmi_line(&mut self) -> Option<bool> {
+        self.bus.nmi_line()
+    }
+}
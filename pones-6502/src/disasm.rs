@@ -0,0 +1,140 @@
+use std::fmt;
+
+use crate::cpu::{Bus, CpuVariant};
+use crate::opcodes::AddrMode;
+use crate::symbols::SymbolTable;
+
+/// A single decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// The address the instruction was decoded at.
+    pub addr: u16,
+    pub mnemonic: &'static str,
+    pub mode: AddrMode,
+    /// Whether the opcode is documented, as opposed to an illegal opcode.
+    pub official: bool,
+    /// The length of the instruction in bytes, including the opcode.
+    pub len: u8,
+    bytes: [u8; 3],
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// The raw bytes of the instruction, including the opcode.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    /// The operand bytes following the opcode.
+    pub fn operand_bytes(&self) -> &[u8] {
+        &self.bytes[1..self.len as usize]
+    }
+
+    /// The operand as a little endian value.
+    pub fn operand(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
+
+    /// The address of the next instruction in memory.
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.len as u16)
+    }
+
+    /// The destination of a relative branch.
+    pub fn branch_target(&self) -> u16 {
+        let offset = match self.mode {
+            AddrMode::ZeroPageRelative => self.bytes[2],
+            _ => self.bytes[1],
+        };
+        self.next_addr().wrapping_add(offset as i8 as u16)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, None)
+    }
+}
+
+impl Instruction {
+    /// Display the instruction with the addresses in its operand named after the nearest
+    /// label in `symbols`, like `JSR print` or `LDA table+2,X`. Addresses without a label
+    /// nearby, and immediate operands, are shown as numbers.
+    pub fn with_symbols<'s>(&'s self, symbols: &'s SymbolTable) -> impl fmt::Display + 's {
+        WithSymbols(self, symbols)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, symbols: Option<&SymbolTable>) -> fmt::Result {
+        use AddrMode::*;
+
+        let name = |addr: u16| symbols.and_then(|symbols| symbols.nearest_name(addr));
+        let zero_page = |addr: u8| name(addr as u16).unwrap_or_else(|| format!("${:02X}", addr));
+        let absolute = |addr: u16| name(addr).unwrap_or_else(|| format!("${:04X}", addr));
+
+        if !self.official {
+            write!(f, "*")?;
+        }
+        write!(f, "{}", self.mnemonic)?;
+        let byte = self.bytes[1];
+        let word = self.operand();
+        match self.mode {
+            Implied => Ok(()),
+            Accumulator => write!(f, " A"),
+            Immediate => write!(f, " #${:02X}", byte),
+            ZeroPage => write!(f, " {}", zero_page(byte)),
+            ZeroPageX => write!(f, " {},X", zero_page(byte)),
+            ZeroPageY => write!(f, " {},Y", zero_page(byte)),
+            Relative => write!(f, " {}", absolute(self.branch_target())),
+            Absolute => write!(f, " {}", absolute(word)),
+            AbsoluteX => write!(f, " {},X", absolute(word)),
+            AbsoluteY => write!(f, " {},Y", absolute(word)),
+            Indirect => write!(f, " ({})", absolute(word)),
+            IndexedIndirect => write!(f, " ({},X)", zero_page(byte)),
+            IndirectIndexed => write!(f, " ({}),Y", zero_page(byte)),
+            ZeroPageIndirect => write!(f, " ({})", zero_page(byte)),
+            AbsoluteIndexedIndirect => write!(f, " ({},X)", absolute(word)),
+            ZeroPageRelative => write!(f, " {},{}", zero_page(byte), absolute(self.branch_target())),
+        }
+    }
+}
+
+struct WithSymbols<'s>(&'s Instruction, &'s SymbolTable);
+
+impl fmt::Display for WithSymbols<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, Some(self.1))
+    }
+}
+
+impl Instruction {
+    /// Decode an instruction for `variant` at `addr` from its bytes.
+    /// Bytes past the end of the instruction are ignored.
+    pub fn decode(variant: CpuVariant, addr: u16, bytes: [u8; 3]) -> Self {
+        let info = &variant.opcodes()[bytes[0] as usize];
+        let mut bytes = bytes;
+        bytes[info.len as usize..].fill(0);
+        Self {
+            addr,
+            mnemonic: info.mnemonic(),
+            mode: info.mode,
+            official: info.official,
+            len: info.len,
+            bytes,
+        }
+    }
+}
+
+/// Decode the instruction for `variant` at `addr` using [`Bus::peek`].
+/// Returns `None` if any of its bytes can't be peeked.
+pub fn disassemble(variant: CpuVariant, bus: &impl Bus, addr: u16) -> Option<Instruction> {
+    let opcode = bus.peek(addr)?;
+    let len = variant.opcodes()[opcode as usize].len;
+    let mut bytes = [opcode, 0, 0];
+    for (i, byte) in bytes.iter_mut().enumerate().take(len as usize).skip(1) {
+        *byte = bus.peek(addr.wrapping_add(i as u16))?;
+    }
+    Some(Instruction::decode(variant, addr, bytes))
+}
@@ -0,0 +1,367 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use crate::cpu::{Bus, Cpu6502};
+use crate::debugger::{AccessKind, Debugger, StopReason, Watchpoint};
+
+const INTERRUPT: u8 = 0x03;
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// How many cycles to run between checks for an interrupt from the client.
+const CONTINUE_CHUNK_CYCLES: u64 = 10_000;
+
+/// A server for the GDB remote serial protocol, debugging a [`Cpu6502`] over a TCP connection.
+///
+/// Registers are numbered A, X, Y, SP, PC, P, and sent in that order by `g`. They're
+/// all 8 bits wide, except PC which is 16 bits and sent little endian like memory.
+///
+/// Memory is read with [`Bus::peek`], so reading addresses that can't be peeked is an error.
+/// Memory is written with [`Bus::write`].
+pub struct GdbStub {
+    stream: BufReader<TcpStream>,
+    pub debugger: Debugger,
+    no_ack: bool,
+    last_reply: Vec<u8>,
+}
+
+enum Action {
+    Reply(String),
+    Resume(Resume),
+    Detach,
+    Kill,
+}
+
+enum Resume {
+    Step,
+    Continue,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Self {
+        Self::with_debugger(stream, Debugger::new())
+    }
+
+    /// Create a stub that keeps the breakpoints and watchpoints set in `debugger`.
+    pub fn with_debugger(stream: TcpStream, debugger: Debugger) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            debugger,
+            no_ack: false,
+            last_reply: Vec::new(),
+        }
+    }
+
+    /// Serve the client until it detaches, kills the session or disconnects.
+    pub fn run(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match self.handle(&packet, cpu, bus) {
+                Action::Reply(reply) => reply,
+                Action::Resume(resume) => {
+                    let stop = match resume {
+                        Resume::Step => Some(self.debugger.step(cpu, bus)),
+                        Resume::Continue => self.resume(cpu, bus)?,
+                    };
+                    stop_reply(stop, self.debugger.watchpoints())
+                }
+                Action::Detach => {
+                    self.write_packet("OK")?;
+                    return Ok(());
+                }
+                Action::Kill => return Ok(()),
+            };
+            self.write_packet(&reply)?;
+        }
+        Ok(())
+    }
+
+    // Continue until a stop, returning `None` if the client interrupted.
+    fn resume(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> io::Result<Option<StopReason>> {
+        loop {
+            let cycle = cpu.cycles + CONTINUE_CHUNK_CYCLES;
+            match self.debugger.run_until_cycle(cpu, bus, cycle) {
+                StopReason::Done => {}
+                stop => return Ok(Some(stop)),
+            }
+            if self.interrupted()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.get_ref().set_nonblocking(true)?;
+        let received = self.stream.fill_buf().map(|buf| buf.first() == Some(&INTERRUPT));
+        self.stream.get_ref().set_nonblocking(false)?;
+        match received {
+            Ok(true) => {
+                self.stream.consume(1);
+                Ok(true)
+            }
+            Ok(false) => Ok(false),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn handle(&mut self, packet: &[u8], cpu: &mut Cpu6502, bus: &mut impl Bus) -> Action {
+        let Ok(packet) = std::str::from_utf8(packet) else {
+            return Action::Reply(String::new());
+        };
+        let command = packet.get(..1).unwrap_or("");
+        let args = packet.get(1..).unwrap_or("");
+        let reply = match command {
+            "?" => Some(format!("S{:02x}", SIGTRAP)),
+            "g" => Some(hex(&registers(cpu))),
+            "G" => parse_hex(args).and_then(|bytes| {
+                let mut regs = registers(cpu);
+                (bytes.len() == regs.len()).then(|| {
+                    regs.copy_from_slice(&bytes);
+                    set_registers(cpu, &regs);
+                    "OK".into()
+                })
+            }),
+            "p" => parse_u16(args).and_then(|reg| register(cpu, reg)).map(|value| hex(&value)),
+            "P" => args.split_once('=').and_then(|(reg, value)| {
+                let reg = parse_u16(reg)?;
+                let value = parse_hex(value)?;
+                set_register(cpu, reg, &value).then(|| "OK".into())
+            }),
+            "m" => parse_addr_len(args).map(|(addr, len)| {
+                let bytes: Option<Vec<_>> = (0..len).map(|i| bus.peek(addr.wrapping_add(i as u16))).collect();
+                bytes.map_or_else(|| "E14".into(), |bytes| hex(&bytes))
+            }),
+            "M" => args.split_once(':').and_then(|(addr_len, data)| {
+                let (addr, len) = parse_addr_len(addr_len)?;
+                let data = parse_hex(data)?;
+                (data.len() == len as usize).then(|| {
+                    for (i, &byte) in data.iter().enumerate() {
+                        bus.write(addr.wrapping_add(i as u16), byte);
+                    }
+                    "OK".into()
+                })
+            }),
+            "s" | "c" => {
+                // Resume at the address given, if any.
+                if !args.is_empty() {
+                    let Some(addr) = parse_u16(args) else {
+                        return Action::Reply("E01".into());
+                    };
+                    cpu.pc = addr;
+                }
+                let resume = if command == "s" { Resume::Step } else { Resume::Continue };
+                return Action::Resume(resume);
+            }
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "H" => Some("OK".into()),
+            "D" => return Action::Detach,
+            "k" => return Action::Kill,
+            _ => match packet {
+                "qAttached" => Some("1".into()),
+                "QStartNoAckMode" => {
+                    self.no_ack = true;
+                    Some("OK".into())
+                }
+                _ if packet.starts_with("qSupported") => Some("PacketSize=1000;QStartNoAckMode+".into()),
+                _ => Some(String::new()),
+            },
+        };
+        Action::Reply(reply.unwrap_or_else(|| "E01".into()))
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut args = args.split(',');
+        let kind = args.next()?;
+        let addr = parse_u16(args.next()?)?;
+        let len = parse_len(args.next()?.split(';').next()?)?;
+        let (read, write) = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(addr);
+                } else {
+                    self.debugger.remove_breakpoint(addr);
+                }
+                return Some("OK".into());
+            }
+            "2" => (false, true),
+            "3" => (true, false),
+            "4" => (true, true),
+            _ => return Some(String::new()),
+        };
+        // Ranges past $FFFF are rejected rather than wrapping around to $0000.
+        let end = u16::try_from(addr as u32 + len.max(1) - 1).ok()?;
+        let watchpoint = Watchpoint {
+            range: addr..=end,
+            read,
+            write,
+            execute: false,
+        };
+        if insert {
+            self.debugger.add_watchpoint(watchpoint);
+        } else {
+            self.debugger.remove_watchpoint(&watchpoint);
+        }
+        Some("OK".into())
+    }
+
+    // Returns `None` once the client disconnects.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(b'-') => {
+                    let reply = self.last_reply.clone();
+                    self.stream.get_mut().write_all(&reply)?;
+                    continue;
+                }
+                // Acks, and interrupts that arrive while the CPU is already stopped.
+                Some(_) => continue,
+            }
+
+            let mut packet = Vec::new();
+            let mut checksum = 0u8;
+            loop {
+                let Some(byte) = self.read_byte()? else {
+                    return Ok(None);
+                };
+                match byte {
+                    b'#' => break,
+                    b'}' => {
+                        let Some(escaped) = self.read_byte()? else {
+                            return Ok(None);
+                        };
+                        checksum = checksum.wrapping_add(byte).wrapping_add(escaped);
+                        packet.push(escaped ^ 0x20);
+                    }
+                    _ => {
+                        checksum = checksum.wrapping_add(byte);
+                        packet.push(byte);
+                    }
+                }
+            }
+            let mut received = [0; 2];
+            self.stream.read_exact(&mut received)?;
+            let received = std::str::from_utf8(&received).ok()
+                .and_then(|received| u8::from_str_radix(received, 16).ok());
+
+            if !self.no_ack {
+                let ack = if received == Some(checksum) { b"+" } else { b"-" };
+                self.stream.get_mut().write_all(ack)?;
+                if received != Some(checksum) {
+                    continue;
+                }
+            }
+            return Ok(Some(packet));
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.last_reply = format!("${}#{:02x}", data, checksum).into_bytes();
+        self.stream.get_mut().write_all(&self.last_reply)
+    }
+}
+
+fn stop_reply(stop: Option<StopReason>, watchpoints: &[Watchpoint]) -> String {
+    match stop {
+        None => format!("S{:02x}", SIGINT),
+        Some(StopReason::Halted(_)) => format!("S{:02x}", SIGILL),
+        Some(StopReason::Watchpoint { addr, kind: kind @ (AccessKind::Read | AccessKind::Write) }) => {
+            // The first watchpoint that matches is the one that was hit.
+            let watchpoint = watchpoints.iter().find(|w| {
+                w.range.contains(&addr) && if kind == AccessKind::Read { w.read } else { w.write }
+            });
+            let name = match watchpoint {
+                Some(w) if w.read && w.write => "awatch",
+                _ if kind == AccessKind::Read => "rwatch",
+                _ => "watch",
+            };
+            format!("T{:02x}{}:{:x};", SIGTRAP, name, addr)
+        }
+        Some(_) => format!("S{:02x}", SIGTRAP),
+    }
+}
+
+fn registers(cpu: &Cpu6502) -> [u8; 7] {
+    let [pc_low, pc_high] = cpu.pc.to_le_bytes();
+    [cpu.reg.a, cpu.reg.x, cpu.reg.y, cpu.sp, pc_low, pc_high, cpu.reg.get_status(false)]
+}
+
+fn set_registers(cpu: &mut Cpu6502, regs: &[u8; 7]) {
+    let [a, x, y, sp, pc_low, pc_high, p] = *regs;
+    cpu.reg.a = a;
+    cpu.reg.x = x;
+    cpu.reg.y = y;
+    cpu.sp = sp;
+    cpu.pc = u16::from_le_bytes([pc_low, pc_high]);
+    cpu.reg.set_status(p);
+}
+
+fn register(cpu: &Cpu6502, reg: u16) -> Option<Vec<u8>> {
+    let regs = registers(cpu);
+    Some(match reg {
+        0..=3 => vec![regs[reg as usize]],
+        4 => regs[4..6].to_vec(),
+        5 => vec![regs[6]],
+        _ => return None,
+    })
+}
+
+fn set_register(cpu: &mut Cpu6502, reg: u16, value: &[u8]) -> bool {
+    let mut regs = registers(cpu);
+    let range = match reg {
+        0..=3 => reg as usize..reg as usize + 1,
+        4 => 4..6,
+        5 => 6..7,
+        _ => return false,
+    };
+    if value.len() != range.len() {
+        return false;
+    }
+    regs[range].copy_from_slice(value);
+    set_registers(cpu, &regs);
+    true
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for byte in bytes {
+        write!(&mut hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_u16(hex: &str) -> Option<u16> {
+    u16::from_str_radix(hex, 16).ok()
+}
+
+// Lengths can cover the whole address space, so up to $10000.
+fn parse_len(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex, 16).ok().filter(|&len| len <= 0x10000)
+}
+
+fn parse_addr_len(args: &str) -> Option<(u16, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((parse_u16(addr)?, parse_len(len)?))
+}
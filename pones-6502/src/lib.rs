@@ -1,4 +1,22 @@
 mod reg_state;
+mod opcodes;
 mod cpu;
+mod state;
+mod disasm;
+mod asm;
+mod trace;
+mod debugger;
+mod gdb;
+mod symbols;
+mod profiler;
 
+pub use opcodes::{AddrMode, MemoryAccess, OpcodeInfo, RegisterSet, CMOS_OPCODES, NMOS_OPCODES};
 pub use cpu::*;
+pub use state::*;
+pub use disasm::*;
+pub use asm::*;
+pub use trace::*;
+pub use debugger::*;
+pub use gdb::*;
+pub use symbols::*;
+pub use profiler::*;
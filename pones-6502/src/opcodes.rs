@@ -0,0 +1,949 @@
+/// Invokes `$callback!` with the NMOS 6502 opcode table. Each entry is the opcode,
+/// the handler on `CpuWithBus` implementing it, the addressing mode, and an
+/// `illegal` marker for undocumented opcodes. Addressing modes are written as:
+/// ```text
+/// #i    - immediate value
+/// d     - zero page address
+/// *+d   - relative address
+/// a     - absolute address
+/// ($a)  - dereference $a
+/// $l,$r - add $l and $r
+/// ```
+/// Handlers without an addressing mode are implied, or operate on the accumulator.
+macro_rules! nmos_opcodes {
+    ($callback:ident) => {
+        $callback! {
+            0x00 brk_implied()
+            0x01 ora("(d,x)")
+            0x02 stp_implied() illegal
+            0x03 slo("(d,x)") illegal
+            0x04 nop("d") illegal
+            0x05 ora("d")
+            0x06 asl("d")
+            0x07 slo("d") illegal
+            0x08 php_implied()
+            0x09 ora("#i")
+            0x0A asl_implied()
+            0x0B anc("#i") illegal
+            0x0C nop("a") illegal
+            0x0D ora("a")
+            0x0E asl("a")
+            0x0F slo("a") illegal
+            0x10 bpl("*+d")
+            0x11 ora("(d),y")
+            0x12 stp_implied() illegal
+            0x13 slo("(d),y") illegal
+            0x14 nop("d,x") illegal
+            0x15 ora("d,x")
+            0x16 asl("d,x")
+            0x17 slo("d,x") illegal
+            0x18 clc_implied()
+            0x19 ora("a,y")
+            0x1A nop_implied() illegal
+            0x1B slo("a,y") illegal
+            0x1C nop("a,x") illegal
+            0x1D ora("a,x")
+            0x1E asl("a,x")
+            0x1F slo("a,x") illegal
+            0x20 jsr("a")
+            0x21 and("(d,x)")
+            0x22 stp_implied() illegal
+            0x23 rla("(d,x)") illegal
+            0x24 bit("d")
+            0x25 and("d")
+            0x26 rol("d")
+            0x27 rla("d") illegal
+            0x28 plp_implied()
+            0x29 and("#i")
+            0x2A rol_implied()
+            0x2B anc("#i") illegal
+            0x2C bit("a")
+            0x2D and("a")
+            0x2E rol("a")
+            0x2F rla("a") illegal
+            0x30 bmi("*+d")
+            0x31 and("(d),y")
+            0x32 stp_implied() illegal
+            0x33 rla("(d),y") illegal
+            0x34 nop("d,x") illegal
+            0x35 and("d,x")
+            0x36 rol("d,x")
+            0x37 rla("d,x") illegal
+            0x38 sec_implied()
+            0x39 and("a,y")
+            0x3A nop_implied() illegal
+            0x3B rla("a,y") illegal
+            0x3C nop("a,x") illegal
+            0x3D and("a,x")
+            0x3E rol("a,x")
+            0x3F rla("a,x") illegal
+            0x40 rti_implied()
+            0x41 eor("(d,x)")
+            0x42 stp_implied() illegal
+            0x43 sre("(d,x)") illegal
+            0x44 nop("d") illegal
+            0x45 eor("d")
+            0x46 lsr("d")
+            0x47 sre("d") illegal
+            0x48 pha_implied()
+            0x49 eor("#i")
+            0x4A lsr_implied()
+            0x4B alr("#i") illegal
+            0x4C jmp("a")
+            0x4D eor("a")
+            0x4E lsr("a")
+            0x4F sre("a") illegal
+            0x50 bvc("*+d")
+            0x51 eor("(d),y")
+            0x52 stp_implied() illegal
+            0x53 sre("(d),y") illegal
+            0x54 nop("d,x") illegal
+            0x55 eor("d,x")
+            0x56 lsr("d,x")
+            0x57 sre("d,x") illegal
+            0x58 cli_implied()
+            0x59 eor("a,y")
+            0x5A nop_implied() illegal
+            0x5B sre("a,y") illegal
+            0x5C nop("a,x") illegal
+            0x5D eor("a,x")
+            0x5E lsr("a,x")
+            0x5F sre("a,x") illegal
+            0x60 rts_implied()
+            0x61 adc("(d,x)")
+            0x62 stp_implied() illegal
+            0x63 rra("(d,x)") illegal
+            0x64 nop("d") illegal
+            0x65 adc("d")
+            0x66 ror("d")
+            0x67 rra("d") illegal
+            0x68 pla_implied()
+            0x69 adc("#i")
+            0x6A ror_implied()
+            0x6B arr("#i") illegal
+            0x6C jmp("(a)")
+            0x6D adc("a")
+            0x6E ror("a")
+            0x6F rra("a") illegal
+            0x70 bvs("*+d")
+            0x71 adc("(d),y")
+            0x72 stp_implied() illegal
+            0x73 rra("(d),y") illegal
+            0x74 nop("d,x") illegal
+            0x75 adc("d,x")
+            0x76 ror("d,x")
+            0x77 rra("d,x") illegal
+            0x78 sei_implied()
+            0x79 adc("a,y")
+            0x7A nop_implied() illegal
+            0x7B rra("a,y") illegal
+            0x7C nop("a,x") illegal
+            0x7D adc("a,x")
+            0x7E ror("a,x")
+            0x7F rra("a,x") illegal
+            0x80 nop("#i") illegal
+            0x81 sta("(d,x)")
+            0x82 nop("#i") illegal
+            0x83 sax("(d,x)") illegal
+            0x84 sty("d")
+            0x85 sta("d")
+            0x86 stx("d")
+            0x87 sax("d") illegal
+            0x88 dey_implied()
+            0x89 nop("#i") illegal
+            0x8A txa_implied()
+            0x8B xaa("#i") illegal
+            0x8C sty("a")
+            0x8D sta("a")
+            0x8E stx("a")
+            0x8F sax("a") illegal
+            0x90 bcc("*+d")
+            0x91 sta("(d),y")
+            0x92 stp_implied() illegal
+            0x93 ahx("(d),y") illegal
+            0x94 sty("d,x")
+            0x95 sta("d,x")
+            0x96 stx("d,y")
+            0x97 sax("d,y") illegal
+            0x98 tya_implied()
+            0x99 sta("a,y")
+            0x9A txs_implied()
+            0x9B tas("a,y") illegal
+            0x9C shy("a,x") illegal
+            0x9D sta("a,x")
+            0x9E shx("a,y") illegal
+            0x9F ahx("a,y") illegal
+            0xA0 ldy("#i")
+            0xA1 lda("(d,x)")
+            0xA2 ldx("#i")
+            0xA3 lax("(d,x)") illegal
+            0xA4 ldy("d")
+            0xA5 lda("d")
+            0xA6 ldx("d")
+            0xA7 lax("d") illegal
+            0xA8 tay_implied()
+            0xA9 lda("#i")
+            0xAA tax_implied()
+            0xAB lxa("#i") illegal
+            0xAC ldy("a")
+            0xAD lda("a")
+            0xAE ldx("a")
+            0xAF lax("a") illegal
+            0xB0 bcs("*+d")
+            0xB1 lda("(d),y")
+            0xB2 stp_implied() illegal
+            0xB3 lax("(d),y") illegal
+            0xB4 ldy("d,x")
+            0xB5 lda("d,x")
+            0xB6 ldx("d,y")
+            0xB7 lax("d,y") illegal
+            0xB8 clv_implied()
+            0xB9 lda("a,y")
+            0xBA tsx_implied()
+            0xBB las("a,y") illegal
+            0xBC ldy("a,x")
+            0xBD lda("a,x")
+            0xBE ldx("a,y")
+            0xBF lax("a,y") illegal
+            0xC0 cpy("#i")
+            0xC1 cmp("(d,x)")
+            0xC2 nop("#i") illegal
+            0xC3 dcp("(d,x)") illegal
+            0xC4 cpy("d")
+            0xC5 cmp("d")
+            0xC6 dec("d")
+            0xC7 dcp("d") illegal
+            0xC8 iny_implied()
+            0xC9 cmp("#i")
+            0xCA dex_implied()
+            0xCB axs("#i") illegal
+            0xCC cpy("a")
+            0xCD cmp("a")
+            0xCE dec("a")
+            0xCF dcp("a") illegal
+            0xD0 bne("*+d")
+            0xD1 cmp("(d),y")
+            0xD2 stp_implied() illegal
+            0xD3 dcp("(d),y") illegal
+            0xD4 nop("d,x") illegal
+            0xD5 cmp("d,x")
+            0xD6 dec("d,x")
+            0xD7 dcp("d,x") illegal
+            0xD8 cld_implied()
+            0xD9 cmp("a,y")
+            0xDA nop_implied() illegal
+            0xDB dcp("a,y") illegal
+            0xDC nop("a,x") illegal
+            0xDD cmp("a,x")
+            0xDE dec("a,x")
+            0xDF dcp("a,x") illegal
+            0xE0 cpx("#i")
+            0xE1 sbc("(d,x)")
+            0xE2 nop("#i") illegal
+            0xE3 isb("(d,x)") illegal
+            0xE4 cpx("d")
+            0xE5 sbc("d")
+            0xE6 inc("d")
+            0xE7 isb("d") illegal
+            0xE8 inx_implied()
+            0xE9 sbc("#i")
+            0xEA nop_implied()
+            0xEB sbc("#i") illegal
+            0xEC cpx("a")
+            0xED sbc("a")
+            0xEE inc("a")
+            0xEF isb("a") illegal
+            0xF0 beq("*+d")
+            0xF1 sbc("(d),y")
+            0xF2 stp_implied() illegal
+            0xF3 isb("(d),y") illegal
+            0xF4 nop("d,x") illegal
+            0xF5 sbc("d,x")
+            0xF6 inc("d,x")
+            0xF7 isb("d,x") illegal
+            0xF8 sed_implied()
+            0xF9 sbc("a,y")
+            0xFA nop_implied() illegal
+            0xFB isb("a,y") illegal
+            0xFC nop("a,x") illegal
+            0xFD sbc("a,x")
+            0xFE inc("a,x")
+            0xFF isb("a,x") illegal
+        }
+    };
+}
+
+pub(crate) use nmos_opcodes;
+
+/// Invokes `$callback!` with the WDC 65C02 opcode table, in the same format as [`nmos_opcodes`].
+/// The 65C02 adds these addressing modes:
+/// ```text
+/// (d)     - dereference zero page address
+/// (a,x)   - dereference absolute address plus X
+/// d,*+d   - zero page address followed by relative address
+/// ```
+/// Undefined opcodes are marked `illegal`, and are all NOPs.
+macro_rules! cmos_opcodes {
+    ($callback:ident) => {
+        $callback! {
+            0x00 brk_implied()
+            0x01 ora("(d,x)")
+            0x02 nop("#i") illegal
+            0x03 nop_1cycle() illegal
+            0x04 tsb("d")
+            0x05 ora("d")
+            0x06 asl("d")
+            0x07 rmb0("d")
+            0x08 php_implied()
+            0x09 ora("#i")
+            0x0A asl_implied()
+            0x0B nop_1cycle() illegal
+            0x0C tsb("a")
+            0x0D ora("a")
+            0x0E asl("a")
+            0x0F bbr0("d,*+d")
+            0x10 bpl("*+d")
+            0x11 ora("(d),y")
+            0x12 ora("(d)")
+            0x13 nop_1cycle() illegal
+            0x14 trb("d")
+            0x15 ora("d,x")
+            0x16 asl("d,x")
+            0x17 rmb1("d")
+            0x18 clc_implied()
+            0x19 ora("a,y")
+            0x1A inc_implied()
+            0x1B nop_1cycle() illegal
+            0x1C trb("a")
+            0x1D ora("a,x")
+            0x1E asl("a,x")
+            0x1F bbr1("d,*+d")
+            0x20 jsr("a")
+            0x21 and("(d,x)")
+            0x22 nop("#i") illegal
+            0x23 nop_1cycle() illegal
+            0x24 bit("d")
+            0x25 and("d")
+            0x26 rol("d")
+            0x27 rmb2("d")
+            0x28 plp_implied()
+            0x29 and("#i")
+            0x2A rol_implied()
+            0x2B nop_1cycle() illegal
+            0x2C bit("a")
+            0x2D and("a")
+            0x2E rol("a")
+            0x2F bbr2("d,*+d")
+            0x30 bmi("*+d")
+            0x31 and("(d),y")
+            0x32 and("(d)")
+            0x33 nop_1cycle() illegal
+            0x34 bit("d,x")
+            0x35 and("d,x")
+            0x36 rol("d,x")
+            0x37 rmb3("d")
+            0x38 sec_implied()
+            0x39 and("a,y")
+            0x3A dec_implied()
+            0x3B nop_1cycle() illegal
+            0x3C bit("a,x")
+            0x3D and("a,x")
+            0x3E rol("a,x")
+            0x3F bbr3("d,*+d")
+            0x40 rti_implied()
+            0x41 eor("(d,x)")
+            0x42 nop("#i") illegal
+            0x43 nop_1cycle() illegal
+            0x44 nop("d") illegal
+            0x45 eor("d")
+            0x46 lsr("d")
+            0x47 rmb4("d")
+            0x48 pha_implied()
+            0x49 eor("#i")
+            0x4A lsr_implied()
+            0x4B nop_1cycle() illegal
+            0x4C jmp("a")
+            0x4D eor("a")
+            0x4E lsr("a")
+            0x4F bbr4("d,*+d")
+            0x50 bvc("*+d")
+            0x51 eor("(d),y")
+            0x52 eor("(d)")
+            0x53 nop_1cycle() illegal
+            0x54 nop("d,x") illegal
+            0x55 eor("d,x")
+            0x56 lsr("d,x")
+            0x57 rmb5("d")
+            0x58 cli_implied()
+            0x59 eor("a,y")
+            0x5A phy_implied()
+            0x5B nop_1cycle() illegal
+            0x5C nop_long("a") illegal
+            0x5D eor("a,x")
+            0x5E lsr("a,x")
+            0x5F bbr5("d,*+d")
+            0x60 rts_implied()
+            0x61 adc("(d,x)")
+            0x62 nop("#i") illegal
+            0x63 nop_1cycle() illegal
+            0x64 stz("d")
+            0x65 adc("d")
+            0x66 ror("d")
+            0x67 rmb6("d")
+            0x68 pla_implied()
+            0x69 adc("#i")
+            0x6A ror_implied()
+            0x6B nop_1cycle() illegal
+            0x6C jmp("(a)")
+            0x6D adc("a")
+            0x6E ror("a")
+            0x6F bbr6("d,*+d")
+            0x70 bvs("*+d")
+            0x71 adc("(d),y")
+            0x72 adc("(d)")
+            0x73 nop_1cycle() illegal
+            0x74 stz("d,x")
+            0x75 adc("d,x")
+            0x76 ror("d,x")
+            0x77 rmb7("d")
+            0x78 sei_implied()
+            0x79 adc("a,y")
+            0x7A ply_implied()
+            0x7B nop_1cycle() illegal
+            0x7C jmp("(a,x)")
+            0x7D adc("a,x")
+            0x7E ror("a,x")
+            0x7F bbr7("d,*+d")
+            0x80 bra("*+d")
+            0x81 sta("(d,x)")
+            0x82 nop("#i") illegal
+            0x83 nop_1cycle() illegal
+            0x84 sty("d")
+            0x85 sta("d")
+            0x86 stx("d")
+            0x87 smb0("d")
+            0x88 dey_implied()
+            0x89 bit_immediate("#i")
+            0x8A txa_implied()
+            0x8B nop_1cycle() illegal
+            0x8C sty("a")
+            0x8D sta("a")
+            0x8E stx("a")
+            0x8F bbs0("d,*+d")
+            0x90 bcc("*+d")
+            0x91 sta("(d),y")
+            0x92 sta("(d)")
+            0x93 nop_1cycle() illegal
+            0x94 sty("d,x")
+            0x95 sta("d,x")
+            0x96 stx("d,y")
+            0x97 smb1("d")
+            0x98 tya_implied()
+            0x99 sta("a,y")
+            0x9A txs_implied()
+            0x9B nop_1cycle() illegal
+            0x9C stz("a")
+            0x9D sta("a,x")
+            0x9E stz("a,x")
+            0x9F bbs1("d,*+d")
+            0xA0 ldy("#i")
+            0xA1 lda("(d,x)")
+            0xA2 ldx("#i")
+            0xA3 nop_1cycle() illegal
+            0xA4 ldy("d")
+            0xA5 lda("d")
+            0xA6 ldx("d")
+            0xA7 smb2("d")
+            0xA8 tay_implied()
+            0xA9 lda("#i")
+            0xAA tax_implied()
+            0xAB nop_1cycle() illegal
+            0xAC ldy("a")
+            0xAD lda("a")
+            0xAE ldx("a")
+            0xAF bbs2("d,*+d")
+            0xB0 bcs("*+d")
+            0xB1 lda("(d),y")
+            0xB2 lda("(d)")
+            0xB3 nop_1cycle() illegal
+            0xB4 ldy("d,x")
+            0xB5 lda("d,x")
+            0xB6 ldx("d,y")
+            0xB7 smb3("d")
+            0xB8 clv_implied()
+            0xB9 lda("a,y")
+            0xBA tsx_implied()
+            0xBB nop_1cycle() illegal
+            0xBC ldy("a,x")
+            0xBD lda("a,x")
+            0xBE ldx("a,y")
+            0xBF bbs3("d,*+d")
+            0xC0 cpy("#i")
+            0xC1 cmp("(d,x)")
+            0xC2 nop("#i") illegal
+            0xC3 nop_1cycle() illegal
+            0xC4 cpy("d")
+            0xC5 cmp("d")
+            0xC6 dec("d")
+            0xC7 smb4("d")
+            0xC8 iny_implied()
+            0xC9 cmp("#i")
+            0xCA dex_implied()
+            0xCB wai_implied()
+            0xCC cpy("a")
+            0xCD cmp("a")
+            0xCE dec("a")
+            0xCF bbs4("d,*+d")
+            0xD0 bne("*+d")
+            0xD1 cmp("(d),y")
+            0xD2 cmp("(d)")
+            0xD3 nop_1cycle() illegal
+            0xD4 nop("d,x") illegal
+            0xD5 cmp("d,x")
+            0xD6 dec("d,x")
+            0xD7 smb5("d")
+            0xD8 cld_implied()
+            0xD9 cmp("a,y")
+            0xDA phx_implied()
+            0xDB stp_implied()
+            0xDC nop("a") illegal
+            0xDD cmp("a,x")
+            0xDE dec("a,x")
+            0xDF bbs5("d,*+d")
+            0xE0 cpx("#i")
+            0xE1 sbc("(d,x)")
+            0xE2 nop("#i") illegal
+            0xE3 nop_1cycle() illegal
+            0xE4 cpx("d")
+            0xE5 sbc("d")
+            0xE6 inc("d")
+            0xE7 smb6("d")
+            0xE8 inx_implied()
+            0xE9 sbc("#i")
+            0xEA nop_implied()
+            0xEB nop_1cycle() illegal
+            0xEC cpx("a")
+            0xED sbc("a")
+            0xEE inc("a")
+            0xEF bbs6("d,*+d")
+            0xF0 beq("*+d")
+            0xF1 sbc("(d),y")
+            0xF2 sbc("(d)")
+            0xF3 nop_1cycle() illegal
+            0xF4 nop("d,x") illegal
+            0xF5 sbc("d,x")
+            0xF6 inc("d,x")
+            0xF7 smb7("d")
+            0xF8 sed_implied()
+            0xF9 sbc("a,y")
+            0xFA plx_implied()
+            0xFB nop_1cycle() illegal
+            0xFC nop("a") illegal
+            0xFD sbc("a,x")
+            0xFE inc("a,x")
+            0xFF bbs7("d,*+d")
+        }
+    };
+}
+
+pub(crate) use cmos_opcodes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddrMode {
+    /// `BRK`
+    Implied,
+    /// `ASL A`
+    Accumulator,
+    /// `LDA #$12`
+    Immediate,
+    /// `LDA $12`
+    ZeroPage,
+    /// `LDA $12,X`
+    ZeroPageX,
+    /// `LDX $12,Y`
+    ZeroPageY,
+    /// `BNE $1234`, stored as a signed offset from the next instruction.
+    Relative,
+    /// `LDA $1234`
+    Absolute,
+    /// `LDA $1234,X`
+    AbsoluteX,
+    /// `LDA $1234,Y`
+    AbsoluteY,
+    /// `JMP ($1234)`
+    Indirect,
+    /// `LDA ($12,X)`
+    IndexedIndirect,
+    /// `LDA ($12),Y`
+    IndirectIndexed,
+    /// `LDA ($12)`, only on the 65C02.
+    ZeroPageIndirect,
+    /// `JMP ($1234,X)`, only on the 65C02.
+    AbsoluteIndexedIndirect,
+    /// `BBR0 $12,$1234`, only on the 65C02. The zero page address is followed by a relative branch offset.
+    ZeroPageRelative,
+}
+
+impl AddrMode {
+    /// The number of operand bytes following the opcode.
+    pub const fn operand_len(self) -> u8 {
+        use AddrMode::*;
+
+        match self {
+            Implied | Accumulator => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | Relative
+                | IndexedIndirect | IndirectIndexed | ZeroPageIndirect => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect
+                | AbsoluteIndexedIndirect | ZeroPageRelative => 2,
+        }
+    }
+}
+
+/// A set of registers and status flags.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegisterSet(u16);
+
+impl RegisterSet {
+    pub const NONE: Self = Self(0);
+    pub const A: Self = Self(1 << 0);
+    pub const X: Self = Self(1 << 1);
+    pub const Y: Self = Self(1 << 2);
+    pub const SP: Self = Self(1 << 3);
+    pub const PC: Self = Self(1 << 4);
+    pub const CARRY: Self = Self(1 << 5);
+    pub const ZERO: Self = Self(1 << 6);
+    pub const INTERRUPT_DISABLE: Self = Self(1 << 7);
+    pub const DECIMAL: Self = Self(1 << 8);
+    pub const OVERFLOW: Self = Self(1 << 9);
+    pub const NEGATIVE: Self = Self(1 << 10);
+    /// Every status flag.
+    pub const FLAGS: Self = Self::CARRY
+        .union(Self::ZERO)
+        .union(Self::INTERRUPT_DISABLE)
+        .union(Self::DECIMAL)
+        .union(Self::OVERFLOW)
+        .union(Self::NEGATIVE);
+
+    const NZ: Self = Self::NEGATIVE.union(Self::ZERO);
+    const NZC: Self = Self::NZ.union(Self::CARRY);
+    const NVZC: Self = Self::NZC.union(Self::OVERFLOW);
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for RegisterSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+/// How an instruction accesses the memory its addressing mode points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryAccess {
+    /// The instruction doesn't access an operand in memory,
+    /// such as implied, immediate, jump and branch instructions.
+    None,
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+/// Static information about an opcode.
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeInfo {
+    mnemonic: [u8; 4],
+    mnemonic_len: u8,
+    pub mode: AddrMode,
+    /// The length of the instruction in bytes, including the opcode.
+    pub len: u8,
+    /// The number of cycles the instruction takes, not counting any penalties.
+    pub cycles: u8,
+    /// Whether the instruction takes an extra cycle when indexing crosses a page.
+    /// Branches take an extra cycle when taken, plus this one if the target is on another page.
+    pub page_cross_penalty: bool,
+    /// Whether the opcode is documented, as opposed to an illegal opcode.
+    pub official: bool,
+    pub access: MemoryAccess,
+    /// The registers and flags the instruction reads, including index registers used for addressing.
+    pub reads: RegisterSet,
+    /// The registers and flags the instruction writes.
+    pub writes: RegisterSet,
+}
+
+impl OpcodeInfo {
+    pub fn mnemonic(&self) -> &str {
+        std::str::from_utf8(&self.mnemonic[..self.mnemonic_len as usize]).unwrap()
+    }
+
+    const fn with_cycles(mut self, cycles: u8) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    const fn new(handler: &str, mode: AddrMode, official: bool, cmos: bool) -> Self {
+        use AddrMode::*;
+        use MemoryAccess::*;
+
+        let (full_mnemonic, mnemonic_len) = full_mnemonic(handler);
+        let mnemonic = [full_mnemonic[0], full_mnemonic[1], full_mnemonic[2]];
+        let (access, mut reads, mut writes) = effects(&mnemonic, mode);
+        // The 65C02 clears the decimal flag when entering an interrupt handler.
+        if cmos && matches!(&mnemonic, b"BRK") {
+            writes = writes.union(RegisterSet::DECIMAL);
+        }
+        reads = reads.union(match mode {
+            ZeroPageX | AbsoluteX | IndexedIndirect | AbsoluteIndexedIndirect => RegisterSet::X,
+            ZeroPageY | AbsoluteY | IndirectIndexed => RegisterSet::Y,
+            Accumulator => RegisterSet::A,
+            _ => RegisterSet::NONE,
+        });
+        if let Accumulator = mode {
+            writes = writes.union(RegisterSet::A);
+        }
+
+        let cycles = match (mode, access) {
+            (Implied, _) => match &mnemonic {
+                b"BRK" => 7,
+                b"RTI" | b"RTS" => 6,
+                b"PLA" | b"PLP" | b"PLX" | b"PLY" => 4,
+                b"PHA" | b"PHP" | b"PHX" | b"PHY" | b"WAI" => 3,
+                _ => 2,
+            },
+            (Accumulator | Immediate | Relative, _) => 2,
+            (Absolute, None) => match &mnemonic {
+                b"JSR" => 6,
+                _ => 3,
+            },
+            // The 65C02 spends an extra cycle fixing the page wrapping bug.
+            (Indirect, _) => if cmos { 6 } else { 5 },
+            (AbsoluteIndexedIndirect, _) => 6,
+            (ZeroPageRelative, _) => 5,
+            (ZeroPageIndirect, _) => 5,
+            // The 65C02 skips fixing up the address for shifts that don't cross a page.
+            (AbsoluteX, ReadModifyWrite) if cmos && !matches!(&mnemonic, b"INC" | b"DEC") => 6,
+            (ZeroPage, Read | Write) => 3,
+            (ZeroPageX | ZeroPageY | Absolute | AbsoluteX | AbsoluteY, Read) => 4,
+            (ZeroPageX | ZeroPageY | Absolute, Write) => 4,
+            (AbsoluteX | AbsoluteY, Write) => 5,
+            (IndirectIndexed, Read) => 5,
+            (IndexedIndirect | IndirectIndexed, Read | Write) => 6,
+            (ZeroPage, _) => 5,
+            (ZeroPageX | ZeroPageY | Absolute, _) => 6,
+            (AbsoluteX | AbsoluteY, _) => 7,
+            (IndexedIndirect | IndirectIndexed, _) => 8,
+        };
+        let page_cross_penalty = match (mode, access) {
+            (Relative | ZeroPageRelative, _) | (AbsoluteX | AbsoluteY | IndirectIndexed, Read) => true,
+            (AbsoluteX, ReadModifyWrite) => cycles == 6,
+            _ => false,
+        };
+
+        Self {
+            mnemonic: full_mnemonic,
+            mnemonic_len,
+            mode,
+            len: 1 + mode.operand_len(),
+            cycles,
+            page_cross_penalty,
+            official,
+            access,
+            reads,
+            writes,
+        }
+    }
+}
+
+// Handlers are named after their mnemonic, so it's just the uppercased first three letters,
+// plus the bit number of the 65C02's bit ops like `BBR0`. Returns the mnemonic and its length.
+const fn full_mnemonic(handler: &str) -> ([u8; 4], u8) {
+    let bytes = handler.as_bytes();
+    let mnemonic = [
+        bytes[0].to_ascii_uppercase(),
+        bytes[1].to_ascii_uppercase(),
+        bytes[2].to_ascii_uppercase(),
+        if bytes.len() > 3 { bytes[3] } else { b' ' },
+    ];
+    let len = if mnemonic[3].is_ascii_digit() { 4 } else { 3 };
+    (mnemonic, len)
+}
+
+// The memory access and the registers read and written by an instruction,
+// not counting anything used by its addressing mode.
+const fn effects(mnemonic: &[u8; 3], mode: AddrMode) -> (MemoryAccess, RegisterSet, RegisterSet) {
+    use MemoryAccess::*;
+
+    const A: RegisterSet = RegisterSet::A;
+    const X: RegisterSet = RegisterSet::X;
+    const Y: RegisterSet = RegisterSet::Y;
+    const SP: RegisterSet = RegisterSet::SP;
+    const PC: RegisterSet = RegisterSet::PC;
+    const C: RegisterSet = RegisterSet::CARRY;
+    const Z: RegisterSet = RegisterSet::ZERO;
+    const I: RegisterSet = RegisterSet::INTERRUPT_DISABLE;
+    const D: RegisterSet = RegisterSet::DECIMAL;
+    const V: RegisterSet = RegisterSet::OVERFLOW;
+    const N: RegisterSet = RegisterSet::NEGATIVE;
+    const FLAGS: RegisterSet = RegisterSet::FLAGS;
+    const NZ: RegisterSet = RegisterSet::NZ;
+    const NZC: RegisterSet = RegisterSet::NZC;
+    const NVZC: RegisterSet = RegisterSet::NVZC;
+    const NONE: RegisterSet = RegisterSet::NONE;
+
+    let immediate = matches!(mode, AddrMode::Immediate);
+    let implied = matches!(mode, AddrMode::Implied | AddrMode::Accumulator);
+    let read = if immediate { None } else { Read };
+    let rmw = if implied { None } else { ReadModifyWrite };
+    match mnemonic {
+        // Loads and stores
+        b"LDA" => (read, NONE, A.union(NZ)),
+        b"LDX" => (read, NONE, X.union(NZ)),
+        b"LDY" => (read, NONE, Y.union(NZ)),
+        b"STA" => (Write, A, NONE),
+        b"STX" => (Write, X, NONE),
+        b"STY" => (Write, Y, NONE),
+        // Arithmetic and bitwise ops
+        b"ADC" | b"SBC" => (read, A.union(C).union(D), A.union(NVZC)),
+        b"AND" | b"ORA" | b"EOR" => (read, A, A.union(NZ)),
+        b"CMP" => (read, A, NZC),
+        b"CPX" => (read, X, NZC),
+        b"CPY" => (read, Y, NZC),
+        b"BIT" if immediate => (None, A, Z),
+        b"BIT" => (read, A, N.union(V).union(Z)),
+        b"ASL" | b"LSR" => (rmw, NONE, NZC),
+        b"ROL" | b"ROR" => (rmw, C, NZC),
+        b"INC" | b"DEC" => (rmw, NONE, NZ),
+        b"INX" | b"DEX" => (None, X, X.union(NZ)),
+        b"INY" | b"DEY" => (None, Y, Y.union(NZ)),
+        // Transfers and stack ops
+        b"TAX" => (None, A, X.union(NZ)),
+        b"TAY" => (None, A, Y.union(NZ)),
+        b"TXA" => (None, X, A.union(NZ)),
+        b"TYA" => (None, Y, A.union(NZ)),
+        b"TSX" => (None, SP, X.union(NZ)),
+        b"TXS" => (None, X, SP),
+        b"PHA" => (None, A.union(SP), SP),
+        b"PHP" => (None, FLAGS.union(SP), SP),
+        b"PLA" => (None, SP, A.union(SP).union(NZ)),
+        b"PLP" => (None, SP, FLAGS.union(SP)),
+        // Control flow
+        b"JMP" => (None, NONE, PC),
+        b"JSR" => (None, PC.union(SP), PC.union(SP)),
+        b"RTS" => (None, SP, PC.union(SP)),
+        b"RTI" => (None, SP, PC.union(SP).union(FLAGS)),
+        b"BRK" => (None, PC.union(SP).union(FLAGS), PC.union(SP).union(I)),
+        b"BPL" | b"BMI" => (None, N.union(PC), PC),
+        b"BVC" | b"BVS" => (None, V.union(PC), PC),
+        b"BCC" | b"BCS" => (None, C.union(PC), PC),
+        b"BNE" | b"BEQ" => (None, Z.union(PC), PC),
+        // Flag ops
+        b"CLC" | b"SEC" => (None, NONE, C),
+        b"CLI" | b"SEI" => (None, NONE, I),
+        b"CLD" | b"SED" => (None, NONE, D),
+        b"CLV" => (None, NONE, V),
+        // 65C02 ops
+        b"BRA" => (None, PC, PC),
+        b"PHX" => (None, X.union(SP), SP),
+        b"PHY" => (None, Y.union(SP), SP),
+        b"PLX" => (None, SP, X.union(SP).union(NZ)),
+        b"PLY" => (None, SP, Y.union(SP).union(NZ)),
+        b"STZ" => (Write, NONE, NONE),
+        b"TRB" | b"TSB" => (ReadModifyWrite, A, Z),
+        b"RMB" | b"SMB" => (ReadModifyWrite, NONE, NONE),
+        b"BBR" | b"BBS" => (Read, PC, PC),
+        // Illegal opcodes
+        b"NOP" => (if implied { None } else { read }, NONE, NONE),
+        b"SLO" | b"SRE" => (ReadModifyWrite, A, A.union(NZC)),
+        b"RLA" => (ReadModifyWrite, A.union(C), A.union(NZC)),
+        b"RRA" | b"ISB" => (ReadModifyWrite, A.union(C).union(D), A.union(NVZC)),
+        b"DCP" => (ReadModifyWrite, A, NZC),
+        b"LAX" => (read, NONE, A.union(X).union(NZ)),
+        b"SAX" | b"AHX" => (Write, A.union(X), NONE),
+        b"TAS" => (Write, A.union(X), SP),
+        b"SHX" => (Write, X, NONE),
+        b"SHY" => (Write, Y, NONE),
+        b"LAS" => (read, SP, A.union(X).union(SP).union(NZ)),
+        b"ANC" | b"ALR" => (None, A, A.union(NZC)),
+        b"ARR" => (None, A.union(C).union(D), A.union(NVZC)),
+        b"XAA" => (None, A.union(X), A.union(NZ)),
+        b"LXA" => (None, A, A.union(X).union(NZ)),
+        b"AXS" => (None, A.union(X), X.union(NZC)),
+        _ => (None, NONE, NONE),
+    }
+}
+
+macro_rules! opcode_info {
+    ($cmos:literal; $($opcode:literal $handler:ident($($addr_mode:tt)*) $($illegal:ident)?)*) => {
+        [$(opcode_info!(@info $cmos; $handler($($addr_mode)*) $($illegal)?),)*]
+    };
+
+    // The 65C02's undefined opcodes that don't take the usual number of cycles for their mode.
+    (@info $cmos:literal; nop_1cycle() illegal) => {
+        OpcodeInfo::new("nop", AddrMode::Implied, false, $cmos).with_cycles(1)
+    };
+    (@info $cmos:literal; nop_long("a") illegal) => {
+        OpcodeInfo::new("nop", AddrMode::Absolute, false, $cmos).with_cycles(8)
+    };
+    (@info $cmos:literal; $handler:ident($($addr_mode:tt)*) $($illegal:ident)?) => {
+        OpcodeInfo::new(
+            stringify!($handler),
+            opcode_info!(@mode $handler $($addr_mode)*),
+            opcode_info!(@official $($illegal)?),
+            $cmos,
+        )
+    };
+
+    (@mode asl_implied) => { AddrMode::Accumulator };
+    (@mode rol_implied) => { AddrMode::Accumulator };
+    (@mode lsr_implied) => { AddrMode::Accumulator };
+    (@mode ror_implied) => { AddrMode::Accumulator };
+    (@mode inc_implied) => { AddrMode::Accumulator };
+    (@mode dec_implied) => { AddrMode::Accumulator };
+    (@mode $handler:ident) => { AddrMode::Implied };
+    (@mode $handler:ident "#i") => { AddrMode::Immediate };
+    (@mode $handler:ident "d") => { AddrMode::ZeroPage };
+    (@mode $handler:ident "d,x") => { AddrMode::ZeroPageX };
+    (@mode $handler:ident "d,y") => { AddrMode::ZeroPageY };
+    (@mode $handler:ident "*+d") => { AddrMode::Relative };
+    (@mode $handler:ident "a") => { AddrMode::Absolute };
+    (@mode $handler:ident "a,x") => { AddrMode::AbsoluteX };
+    (@mode $handler:ident "a,y") => { AddrMode::AbsoluteY };
+    (@mode $handler:ident "(a)") => { AddrMode::Indirect };
+    (@mode $handler:ident "(d,x)") => { AddrMode::IndexedIndirect };
+    (@mode $handler:ident "(d),y") => { AddrMode::IndirectIndexed };
+    (@mode $handler:ident "(d)") => { AddrMode::ZeroPageIndirect };
+    (@mode $handler:ident "(a,x)") => { AddrMode::AbsoluteIndexedIndirect };
+    (@mode $handler:ident "d,*+d") => { AddrMode::ZeroPageRelative };
+
+    (@official) => { true };
+    (@official illegal) => { false };
+}
+
+macro_rules! nmos_opcode_info {
+    ($($entries:tt)*) => { opcode_info!(false; $($entries)*) };
+}
+
+macro_rules! cmos_opcode_info {
+    ($($entries:tt)*) => { opcode_info!(true; $($entries)*) };
+}
+
+/// Information about every opcode of the NMOS 6502, indexed by opcode.
+pub static NMOS_OPCODES: [OpcodeInfo; 256] = nmos_opcodes!(nmos_opcode_info);
+
+/// Information about every opcode of the WDC 65C02, indexed by opcode.
+pub static CMOS_OPCODES: [OpcodeInfo; 256] = cmos_opcodes!(cmos_opcode_info);
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::cpu::{Bus, Cpu6502, CpuHalted, ReadKind};
+
+const BRK_OPCODE: u8 = 0x00;
+const JSR_OPCODE: u8 = 0x20;
+const RTI_OPCODE: u8 = 0x40;
+const RTS_OPCODE: u8 = 0x60;
+
+/// The time spent on the instruction at an address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstructionProfile {
+    pub executions: u64,
+    pub cycles: u64,
+}
+
+/// The time spent in a subroutine or interrupt handler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoutineProfile {
+    pub calls: u64,
+    /// Cycles spent in the routine itself, including the interrupt sequence for handlers.
+    pub self_cycles: u64,
+    /// Cycles spent in the routine and everything it called, counting recursive calls once.
+    pub total_cycles: u64,
+}
+
+struct Frame {
+    routine: u16,
+    // The stack pointer before the call, which returning restores.
+    sp: u8,
+    start_cycles: u64,
+}
+
+/// Runs a [`Cpu6502`] on any [`Bus`], counting the executions and cycles of each instruction
+/// and building a call graph from `JSR`, `BRK` and interrupts to the matching `RTS` or `RTI`.
+///
+/// Routines are identified by their entry point, and the code running when profiling
+/// started counts as a routine that was called once. Returns are matched to calls by the
+/// stack pointer, so a routine that drops its return address and jumps elsewhere stays
+/// on the call stack until the stack is reused by a later call or return.
+#[derive(Default)]
+pub struct Profiler {
+    instructions: HashMap<u16, InstructionProfile>,
+    routines: HashMap<u16, RoutineProfile>,
+    calls: HashMap<(u16, u16), u64>,
+    stack: Vec<Frame>,
+    cycles: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget everything profiled so far, e.g. to skip past a game's startup.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Execute a single instruction, or an interrupt sequence, and profile it.
+    pub fn step(&mut self, cpu: &mut Cpu6502, bus: &mut impl Bus) -> Result<u32, CpuHalted> {
+        if self.stack.is_empty() {
+            self.routines.entry(cpu.pc).or_default().calls += 1;
+            self.stack.push(Frame { routine: cpu.pc, sp: cpu.sp, start_cycles: self.cycles });
+        }
+        let pc = cpu.pc;
+        let sp = cpu.sp;
+        let was_waiting = cpu.is_waiting();
+        let mut bus = ProfileBus { bus, opcode: None };
+        let cycles = cpu.step(&mut bus)?;
+        self.cycles += cycles as u64;
+
+        let caller = self.current_routine();
+        if was_waiting && cpu.is_waiting() {
+            // Cycles spent waiting are counted against the WAI.
+            self.instructions.entry(pc.wrapping_sub(1)).or_default().cycles += cycles as u64;
+            self.routines.entry(caller).or_default().self_cycles += cycles as u64;
+        } else if bus.opcode.is_none() {
+            // No opcode was fetched, so the step ran an interrupt sequence.
+            self.call(cpu.pc, sp, self.cycles - cycles as u64);
+            self.routines.entry(cpu.pc).or_default().self_cycles += cycles as u64;
+        } else {
+            let instruction = self.instructions.entry(pc).or_default();
+            instruction.executions += 1;
+            instruction.cycles += cycles as u64;
+            self.routines.entry(caller).or_default().self_cycles += cycles as u64;
+            match bus.opcode {
+                Some(JSR_OPCODE | BRK_OPCODE) => self.call(cpu.pc, sp, self.cycles),
+                Some(RTS_OPCODE | RTI_OPCODE) => self.unwind(cpu.sp),
+                _ => {}
+            }
+        }
+        Ok(cycles)
+    }
+
+    /// The total number of cycles profiled.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The profile of the instruction at `addr`.
+    pub fn instruction(&self, addr: u16) -> InstructionProfile {
+        self.instructions.get(&addr).copied().unwrap_or_default()
+    }
+
+    /// The address and profile of each instruction that was executed.
+    pub fn instructions(&self) -> impl Iterator<Item = (u16, InstructionProfile)> + '_ {
+        self.instructions.iter().map(|(&addr, &profile)| (addr, profile))
+    }
+
+    /// The profile of each routine, by entry point. Routines that are still running
+    /// include the time they've spent so far in their total.
+    pub fn routines(&self) -> HashMap<u16, RoutineProfile> {
+        let mut routines = self.routines.clone();
+        for (i, frame) in self.stack.iter().enumerate() {
+            if self.stack[..i].iter().all(|f| f.routine != frame.routine) {
+                routines.entry(frame.routine).or_default().total_cycles += self.cycles - frame.start_cycles;
+            }
+        }
+        routines
+    }
+
+    /// The number of times each routine was called from each other routine, by the entry
+    /// points of the caller and the callee.
+    pub fn calls(&self) -> &HashMap<(u16, u16), u64> {
+        &self.calls
+    }
+
+    /// Write a report of the instructions that took the most cycles,
+    /// followed by each routine with its callers and callees.
+    /// Addresses are annotated with the name `label` returns for them, if any,
+    /// such as [`SymbolTable::nearest_name`](crate::SymbolTable::nearest_name).
+    pub fn write_report(&self, out: &mut impl Write, label: impl Fn(u16) -> Option<String>) -> io::Result<()> {
+        let name = |addr: u16| match label(addr) {
+            Some(label) => format!("${:04X} {}", addr, label),
+            None => format!("${:04X}", addr),
+        };
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.cycles.max(1) as f64;
+
+        writeln!(out, "{} cycles profiled", self.cycles)?;
+        writeln!(out)?;
+        writeln!(out, "{:>12} {:>6} {:>10}  Instruction", "Cycles", "%", "Executions")?;
+        let mut instructions: Vec<_> = self.instructions().collect();
+        instructions.sort_by_key(|&(addr, profile)| (std::cmp::Reverse(profile.cycles), addr));
+        for (addr, profile) in instructions {
+            writeln!(
+                out,
+                "{:>12} {:>5.1}% {:>10}  {}",
+                profile.cycles,
+                percent(profile.cycles),
+                profile.executions,
+                name(addr),
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "{:>12} {:>6} {:>12} {:>6} {:>8}  Routine", "Total", "%", "Self", "%", "Calls")?;
+        let mut routines: Vec<_> = self.routines().into_iter().collect();
+        routines.sort_by_key(|&(addr, profile)| (std::cmp::Reverse(profile.total_cycles), addr));
+        let mut calls: Vec<_> = self.calls.iter().map(|(&edge, &count)| (edge, count)).collect();
+        calls.sort_by_key(|&(edge, count)| (std::cmp::Reverse(count), edge));
+        for (addr, profile) in routines {
+            writeln!(
+                out,
+                "{:>12} {:>5.1}% {:>12} {:>5.1}% {:>8}  {}",
+                profile.total_cycles,
+                percent(profile.total_cycles),
+                profile.self_cycles,
+                percent(profile.self_cycles),
+                profile.calls,
+                name(addr),
+            )?;
+            for &((caller, callee), count) in &calls {
+                if callee == addr {
+                    writeln!(out, "{:>48}  <- {}", count, name(caller))?;
+                }
+            }
+            for &((caller, callee), count) in &calls {
+                if caller == addr {
+                    writeln!(out, "{:>48}  -> {}", count, name(callee))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn current_routine(&self) -> u16 {
+        self.stack.last().map_or(0, |frame| frame.routine)
+    }
+
+    // `sp` is the stack pointer before the call pushed anything.
+    fn call(&mut self, routine: u16, sp: u8, start_cycles: u64) {
+        // Any frame this call overwrote the return address of is never returning.
+        self.unwind(sp);
+        let caller = self.current_routine();
+        self.routines.entry(routine).or_default().calls += 1;
+        *self.calls.entry((caller, routine)).or_default() += 1;
+        self.stack.push(Frame { routine, sp, start_cycles });
+    }
+
+    // Pops the frames a stack pointer of `sp` has returned from. The first frame is never popped.
+    fn unwind(&mut self, sp: u8) {
+        while self.stack.len() > 1 && self.stack.last().is_some_and(|frame| frame.sp <= sp) {
+            let frame = self.stack.pop().unwrap();
+            if self.stack.iter().all(|f| f.routine != frame.routine) {
+                self.routines.entry(frame.routine).or_default().total_cycles += self.cycles - frame.start_cycles;
+            }
+        }
+    }
+}
+
+struct ProfileBus<'p, B> {
+    bus: &'p mut B,
+    opcode: Option<u8>,
+}
+
+impl<B: Bus> Bus for ProfileBus<'_, B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.read_as(addr, ReadKind::Data)
+    }
+
+    fn read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        let value = self.bus.read_as(addr, kind);
+        if kind == ReadKind::Opcode {
+            self.opcode = Some(value);
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.bus.write(addr, value);
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.bus.peek(addr)
+    }
+
+    fn nmi_line(&mut self) -> Option<bool> {
+        self.bus.nmi_line()
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::cpu::{Cpu6502, CpuHalted, CpuVariant};
+
+const STATE_MAGIC: [u8; 4] = *b"6502";
+const STATE_VERSION: u8 = 1;
+
+/// Returned by [`Cpu6502::read_state`] if the state can't be loaded.
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    /// The data doesn't start with the magic bytes of a CPU state.
+    BadMagic,
+    /// The state was saved by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// A field has a value no CPU state could have.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read cpu state: {}", e),
+            Self::BadMagic => write!(f, "not a cpu state"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported cpu state version {}", version),
+            Self::Invalid(field) => write!(f, "invalid {} in cpu state", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl Cpu6502 {
+    /// Write the full state of the CPU, including pending interrupts and the cycle count,
+    /// in a versioned binary format. States written by older versions can always be read.
+    ///
+    /// The state has a fixed length for each version, so it can be embedded in larger states.
+    pub fn write_state(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&STATE_MAGIC)?;
+        w.write_all(&[STATE_VERSION])?;
+        w.write_all(&[
+            self.reg.a,
+            self.reg.x,
+            self.reg.y,
+            self.reg.get_status(false),
+            self.sp,
+        ])?;
+        w.write_all(&self.pc.to_le_bytes())?;
+        w.write_all(&self.cycles.to_le_bytes())?;
+        w.write_all(&[
+            self.cycle_accurate as u8,
+            self.unstable_magic,
+            self.halted.is_some() as u8,
+        ])?;
+        w.write_all(&self.halted.map_or(0, |halted| halted.pc).to_le_bytes())?;
+        let interrupt_disable_at_poll = match self.interrupt_disable_at_poll {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        };
+        w.write_all(&[
+            self.irq_sources,
+            self.nmi_line as u8,
+            self.nmi_pending as u8,
+            interrupt_disable_at_poll,
+            self.skip_interrupt_poll as u8,
+        ])?;
+        let variant = match self.variant {
+            CpuVariant::Nmos6502 => 0,
+            CpuVariant::Wdc65C02 => 1,
+            CpuVariant::Ricoh2A03 => 2,
+            CpuVariant::Mos6507 => 3,
+        };
+        w.write_all(&[variant, self.waiting as u8])
+    }
+
+    /// Read a state written by [`Cpu6502::write_state`].
+    pub fn read_state(r: &mut impl Read) -> Result<Self, StateError> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if magic != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = read_u8(r)?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mut cpu = Cpu6502::new();
+        cpu.reg.a = read_u8(r)?;
+        cpu.reg.x = read_u8(r)?;
+        cpu.reg.y = read_u8(r)?;
+        cpu.reg.set_status(read_u8(r)?);
+        cpu.sp = read_u8(r)?;
+        let mut pc = [0; 2];
+        r.read_exact(&mut pc)?;
+        cpu.pc = u16::from_le_bytes(pc);
+        let mut cycles = [0; 8];
+        r.read_exact(&mut cycles)?;
+        cpu.cycles = u64::from_le_bytes(cycles);
+        cpu.cycle_accurate = read_bool(r, "cycle accuracy flag")?;
+        cpu.unstable_magic = read_u8(r)?;
+        let halted = read_bool(r, "halt flag")?;
+        let mut halted_pc = [0; 2];
+        r.read_exact(&mut halted_pc)?;
+        cpu.halted = halted.then_some(CpuHalted { pc: u16::from_le_bytes(halted_pc) });
+        cpu.irq_sources = read_u8(r)?;
+        cpu.nmi_line = read_bool(r, "nmi line")?;
+        cpu.nmi_pending = read_bool(r, "pending nmi flag")?;
+        cpu.interrupt_disable_at_poll = match read_u8(r)? {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => return Err(StateError::Invalid("interrupt poll state")),
+        };
+        cpu.skip_interrupt_poll = read_bool(r, "interrupt poll state")?;
+        cpu.variant = match read_u8(r)? {
+            0 => CpuVariant::Nmos6502,
+            1 => CpuVariant::Wdc65C02,
+            2 => CpuVariant::Ricoh2A03,
+            3 => CpuVariant::Mos6507,
+            _ => return Err(StateError::Invalid("cpu variant")),
+        };
+        cpu.waiting = read_bool(r, "wait flag")?;
+        Ok(cpu)
+    }
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_bool(r: &mut impl Read, field: &'static str) -> Result<bool, StateError> {
+    match read_u8(r)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(StateError::Invalid(field)),
+    }
+}
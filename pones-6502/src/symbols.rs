@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+// How far past a label an address can be and still be named after it.
+const MAX_OFFSET: u16 = 0xFF;
+
+// Where the source starts in an AS65 listing line, after the address and bytes.
+const LST_SOURCE_COLUMN: usize = 24;
+
+/// Labels for addresses, for showing `label+offset` names in place of raw addresses.
+///
+/// Labels can be loaded from FCEUX `.nl` files, ld65 `.dbg` files and AS65 listings,
+/// or inserted directly. Only labels for addresses are loaded, not other constants.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    labels: BTreeMap<u16, String>,
+    addrs: HashMap<String, u16>,
+}
+
+/// Returned when a symbol file can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    /// The line number, starting from 1.
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse an FCEUX `.nl` file, made of lines like `$C000#Label#Comment`.
+    /// Array entries like `$0200/10#Buffer#` label the start of the array.
+    /// Lines without a label are skipped.
+    pub fn parse_nl(text: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+        table.add_nl(text)?;
+        Ok(table)
+    }
+
+    /// Add the labels from an FCEUX `.nl` file. FCEUX keeps a file per bank as well
+    /// as one for RAM, which can all be added to the same table.
+    pub fn add_nl(&mut self, text: &str) -> Result<(), SymbolError> {
+        for (i, line) in text.lines().enumerate() {
+            let error = |message| SymbolError { line: i + 1, message };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(3, '#');
+            let addr = fields.next().unwrap_or_default();
+            let addr = addr.strip_prefix('$').ok_or(error("expected $ before the address"))?;
+            let addr = addr.split_once('/').map_or(addr, |(addr, _size)| addr);
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| error("invalid address"))?;
+            let label = fields.next().ok_or(error("expected # after the address"))?;
+            if !label.is_empty() {
+                self.insert(addr, label);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse an ld65 debug info file, as written by `ld65 --dbgfile`. The labels
+    /// are taken from its `sym` lines, leaving out cheap local labels like `@loop`.
+    pub fn parse_dbg(text: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+        table.add_dbg(text)?;
+        Ok(table)
+    }
+
+    /// Add the labels from an ld65 debug info file.
+    pub fn add_dbg(&mut self, text: &str) -> Result<(), SymbolError> {
+        for (i, line) in text.lines().enumerate() {
+            let error = |message| SymbolError { line: i + 1, message };
+            let Some(fields) = line.strip_prefix("sym\t") else {
+                continue;
+            };
+            let mut name = None;
+            let mut value = None;
+            let mut is_label = false;
+            let mut is_local = false;
+            for field in fields.split(',') {
+                let (key, field_value) = field.split_once('=').ok_or(error("expected key=value"))?;
+                match key {
+                    "name" => name = Some(field_value.trim_matches('"')),
+                    "val" => value = Some(field_value),
+                    "type" => is_label = field_value == "lab",
+                    "parent" => is_local = true,
+                    _ => {}
+                }
+            }
+            if !is_label || is_local {
+                continue;
+            }
+            let name = name.ok_or(error("label without a name"))?;
+            let value = value.ok_or(error("label without a value"))?;
+            let value = value.strip_prefix("0x").ok_or(error("expected 0x before the value"))?;
+            let addr = u16::from_str_radix(value, 16).map_err(|_| error("invalid address"))?;
+            self.insert(addr, name);
+        }
+        Ok(())
+    }
+
+    /// Parse a listing written by Frank A. Kingswood's AS65 assembler, like the ones
+    /// for Klaus Dormann's tests. Labels defined inside macro expansions are left out.
+    pub fn parse_lst(text: &str) -> Self {
+        let mut table = Self::new();
+        table.add_lst(text);
+        table
+    }
+
+    /// Add the labels from an AS65 listing.
+    pub fn add_lst(&mut self, text: &str) {
+        for line in text.lines() {
+            // Lines for code and data start with their address, like `0400 : d8    start   cld`.
+            // Macro expansions have a `>` just before the source.
+            let Some(addr) = line.get(..4).filter(|_| line.get(4..7) == Some(" : ")) else {
+                continue;
+            };
+            let Ok(addr) = u16::from_str_radix(addr, 16) else {
+                continue;
+            };
+            if line.as_bytes().get(LST_SOURCE_COLUMN - 1) == Some(&b'>') {
+                continue;
+            }
+            // Labels start in the first column of the source, where instructions are indented.
+            let source = line.get(LST_SOURCE_COLUMN..).unwrap_or_default();
+            let label = source.split(|c: char| c.is_whitespace() || c == ';').next().unwrap_or_default();
+            let label = label.strip_suffix(':').unwrap_or(label);
+            if label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                self.insert(addr, label);
+            }
+        }
+    }
+
+    /// Label `addr`. If it already has a label, the first one is kept for naming it,
+    /// but the new one can still be looked up with [`SymbolTable::addr`].
+    pub fn insert(&mut self, addr: u16, label: &str) {
+        self.labels.entry(addr).or_insert_with(|| label.to_string());
+        self.addrs.entry(label.to_string()).or_insert(addr);
+    }
+
+    /// The label at exactly `addr`.
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// The address of a label.
+    pub fn addr(&self, label: &str) -> Option<u16> {
+        self.addrs.get(label).copied()
+    }
+
+    /// The closest label at or before `addr`, and how far past it `addr` is.
+    /// Labels more than 255 bytes before `addr` aren't used.
+    pub fn nearest(&self, addr: u16) -> Option<(&str, u16)> {
+        let (&label_addr, label) = self.labels.range(..=addr).next_back()?;
+        let offset = addr - label_addr;
+        (offset <= MAX_OFFSET).then_some((label.as_str(), offset))
+    }
+
+    /// Name `addr` as `label` or `label+offset` after the nearest label, if there is one.
+    pub fn nearest_name(&self, addr: u16) -> Option<String> {
+        match self.nearest(addr)? {
+            (label, 0) => Some(label.to_string()),
+            (label, offset) => Some(format!("{}+{}", label, offset)),
+        }
+    }
+
+    /// Name `addr` as `label` or `label+offset`, falling back to `$XXXX`.
+    pub fn name(&self, addr: u16) -> String {
+        self.nearest_name(addr).unwrap_or_else(|| format!("${:04X}", addr))
+    }
+
+    /// The number of labelled addresses.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
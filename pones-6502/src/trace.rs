@@ -0,0 +1,135 @@
+use std::fmt::Write;
+
+use crate::cpu::{Bus, Cpu6502, CpuVariant};
+use crate::disasm::Instruction;
+use crate::opcodes::AddrMode;
+use crate::symbols::SymbolTable;
+
+/// Format the state of the CPU before it executes the instruction at PC, as a line
+/// of a Nintendulator log (the format `nestest.log` is in). `ppu` is the PPU position
+/// as `(scanline, dot)`, and the `PPU:` column is left out if there is none.
+///
+/// Memory is read through [`Bus::peek`]. Like Nintendulator, values that can't be peeked are shown as `FF`.
+pub fn trace_line(cpu: &Cpu6502, bus: &impl Bus, ppu: Option<(u16, u16)>) -> String {
+    format_trace_line(cpu, bus, ppu, None)
+}
+
+/// Like [`trace_line`], but with the addresses in the operand named after the nearest label
+/// in `symbols`, as in [`Instruction::with_symbols`]. Effective addresses are left as numbers.
+/// Labels can make the disassembly wider than its column, which pushes the registers along.
+pub fn trace_line_with_symbols(
+    cpu: &Cpu6502,
+    bus: &impl Bus,
+    ppu: Option<(u16, u16)>,
+    symbols: &SymbolTable,
+) -> String {
+    format_trace_line(cpu, bus, ppu, Some(symbols))
+}
+
+fn format_trace_line(cpu: &Cpu6502, bus: &impl Bus, ppu: Option<(u16, u16)>, symbols: Option<&SymbolTable>) -> String {
+    let read = |addr: u16| bus.peek(addr).unwrap_or(0xFF);
+    let bytes = [read(cpu.pc), read(cpu.pc.wrapping_add(1)), read(cpu.pc.wrapping_add(2))];
+    let instr = Instruction::decode(cpu.variant, cpu.pc, bytes);
+    let mut hex = String::new();
+    for byte in instr.bytes() {
+        write!(&mut hex, "{:02X} ", byte).unwrap();
+    }
+    let illegal_marker = if instr.official { ' ' } else { '*' };
+    let disasm = format!("{} {}", instr.mnemonic, annotated_operand(cpu, read, &instr, symbols));
+
+    let mut line = format!(
+        "{:04X}  {:<9}{}{:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+        cpu.pc,
+        hex,
+        illegal_marker,
+        disasm.trim_end(),
+        cpu.reg.a,
+        cpu.reg.x,
+        cpu.reg.y,
+        cpu.reg.get_status(false),
+        cpu.sp,
+    );
+    if let Some((scanline, dot)) = ppu {
+        write!(&mut line, " PPU:{:>3},{:>3}", scanline, dot).unwrap();
+    }
+    write!(&mut line, " CYC:{}", cpu.cycles).unwrap();
+    line
+}
+
+// The operand, followed by the effective address and the value at it.
+fn annotated_operand(
+    cpu: &Cpu6502,
+    read: impl Fn(u16) -> u8,
+    instr: &Instruction,
+    symbols: Option<&SymbolTable>,
+) -> String {
+    use AddrMode::*;
+
+    let name = |addr: u16| symbols.and_then(|symbols| symbols.nearest_name(addr));
+    let zero_page = |addr: u8| name(addr as u16).unwrap_or_else(|| format!("${:02X}", addr));
+    let absolute = |addr: u16| name(addr).unwrap_or_else(|| format!("${:04X}", addr));
+
+    let byte = instr.operand_bytes().first().copied().unwrap_or(0);
+    let word = instr.operand();
+    let zero_page_u16 = |addr: u8| {
+        u16::from_le_bytes([read(addr as u16), read(addr.wrapping_add(1) as u16)])
+    };
+    match instr.mode {
+        Implied => String::new(),
+        Accumulator => "A".into(),
+        Immediate => format!("#${:02X}", byte),
+        ZeroPage => format!("{} = {:02X}", zero_page(byte), read(byte as u16)),
+        ZeroPageX => {
+            let addr = byte.wrapping_add(cpu.reg.x);
+            format!("{},X @ {:02X} = {:02X}", zero_page(byte), addr, read(addr as u16))
+        }
+        ZeroPageY => {
+            let addr = byte.wrapping_add(cpu.reg.y);
+            format!("{},Y @ {:02X} = {:02X}", zero_page(byte), addr, read(addr as u16))
+        }
+        Relative => absolute(instr.branch_target()),
+        Absolute if matches!(instr.mnemonic, "JMP" | "JSR") => absolute(word),
+        Absolute => format!("{} = {:02X}", absolute(word), read(word)),
+        AbsoluteX => {
+            let addr = word.wrapping_add(cpu.reg.x as u16);
+            format!("{},X @ {:04X} = {:02X}", absolute(word), addr, read(addr))
+        }
+        AbsoluteY => {
+            let addr = word.wrapping_add(cpu.reg.y as u16);
+            format!("{},Y @ {:04X} = {:02X}", absolute(word), addr, read(addr))
+        }
+        Indirect if cpu.variant == CpuVariant::Wdc65C02 => {
+            let target = u16::from_le_bytes([read(word), read(word.wrapping_add(1))]);
+            format!("({}) = {:04X}", absolute(word), target)
+        }
+        Indirect => {
+            // The high byte is fetched without carrying into the high byte of the pointer.
+            let [low, high] = word.to_le_bytes();
+            let high_addr = u16::from_le_bytes([low.wrapping_add(1), high]);
+            let target = u16::from_le_bytes([read(word), read(high_addr)]);
+            format!("({}) = {:04X}", absolute(word), target)
+        }
+        IndexedIndirect => {
+            let ptr = byte.wrapping_add(cpu.reg.x);
+            let addr = zero_page_u16(ptr);
+            format!("({},X) @ {:02X} = {:04X} = {:02X}", zero_page(byte), ptr, addr, read(addr))
+        }
+        IndirectIndexed => {
+            let base = zero_page_u16(byte);
+            let addr = base.wrapping_add(cpu.reg.y as u16);
+            format!("({}),Y = {:04X} @ {:04X} = {:02X}", zero_page(byte), base, addr, read(addr))
+        }
+        ZeroPageIndirect => {
+            let addr = zero_page_u16(byte);
+            format!("({}) = {:04X} = {:02X}", zero_page(byte), addr, read(addr))
+        }
+        AbsoluteIndexedIndirect => {
+            let ptr = word.wrapping_add(cpu.reg.x as u16);
+            let target = u16::from_le_bytes([read(ptr), read(ptr.wrapping_add(1))]);
+            format!("({},X) @ {:04X} = {:04X}", absolute(word), ptr, target)
+        }
+        ZeroPageRelative => {
+            format!("{} = {:02X},{}", zero_page(byte), read(byte as u16), absolute(instr.branch_target()))
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use pones_6502::{assemble, AsmErrorKind, Chunk, Cpu6502, CpuVariant};
+
+mod common;
+use common::Memory;
+
+fn bytes(variant: CpuVariant, source: &str) -> Vec<u8> {
+    let assembly = assemble(variant, source).unwrap_or_else(|e| panic!("{}", e));
+    assembly.chunks.into_iter().flat_map(|chunk| chunk.bytes).collect()
+}
+
+fn error(source: &str) -> AsmErrorKind {
+    assemble(CpuVariant::Nmos6502, source).unwrap_err().kind
+}
+
+#[test]
+fn addressing_modes() {
+    let source = "
+        .org $0200
+        nop
+        asl
+        asl a
+        lda #$12
+        lda $12
+        lda $12,x
+        ldx $12,y
+        lda $1234
+        lda $1234,x
+        lda $1234,y
+        jmp ($1234)
+        lda ($12,x)
+        lda ($12),y
+        lda a:$12
+    loop:
+        bne loop
+    ";
+    assert_eq!(bytes(CpuVariant::Nmos6502, source), [
+        0xEA,
+        0x0A,
+        0x0A,
+        0xA9, 0x12,
+        0xA5, 0x12,
+        0xB5, 0x12,
+        0xB6, 0x12,
+        0xAD, 0x34, 0x12,
+        0xBD, 0x34, 0x12,
+        0xB9, 0x34, 0x12,
+        0x6C, 0x34, 0x12,
+        0xA1, 0x12,
+        0xB1, 0x12,
+        0xAD, 0x12, 0x00,
+        0xD0, 0xFE,
+    ]);
+}
+
+#[test]
+fn cmos_addressing_modes() {
+    let source = "
+        .org $0200
+    start:
+        lda ($12)
+        jmp ($1234,x)
+        inc a
+        bbs7 $12,start
+        stz $12
+    ";
+    assert_eq!(bytes(CpuVariant::Wdc65C02, source), [
+        0xB2, 0x12,
+        0x7C, 0x34, 0x12,
+        0x1A,
+        0xFF, 0x12, 0xF7,
+        0x64, 0x12,
+    ]);
+    assert_eq!(
+        assemble(CpuVariant::Nmos6502, "lda ($12)").unwrap_err().kind,
+        AsmErrorKind::InvalidAddressingMode("LDA".into()),
+    );
+}
+
+#[test]
+fn symbols_and_expressions() {
+    let source = "
+        zp = $80
+        .org $C000
+        lda zp+1          ; defined before use, so zero page
+        sta later         ; forward reference, so absolute
+        ldx #<table
+        ldy #>table
+        lda #(2+3)*4
+        lda #1<<4|1
+        lda #'A'-$20
+        lda #-1
+    later = $10
+    table: .word *, table
+    ";
+    let assembly = assemble(CpuVariant::Nmos6502, source).unwrap();
+    assert_eq!(assembly.symbol("table"), Some(0xC011));
+    assert_eq!(assembly.chunks, [Chunk {
+        addr: 0xC000,
+        bytes: vec![
+            0xA5, 0x81,
+            0x8D, 0x10, 0x00,
+            0xA2, 0x11,
+            0xA0, 0xC0,
+            0xA9, 20,
+            0xA9, 0x11,
+            0xA9, 0x21,
+            0xA9, 0xFF,
+            0x11, 0xC0, 0x11, 0xC0,
+        ],
+    }]);
+}
+
+#[test]
+fn directives() {
+    let source = r#"
+        .org $10
+        .byte 1, "AB", 'C'
+        .res 2
+        .res 2, $EA
+        .org $20
+        .if 1 > 2
+        .byte 0
+        .else
+        .word $1234
+        .endif
+        .end
+        this isn't assembled
+    "#;
+    let assembly = assemble(CpuVariant::Nmos6502, source).unwrap();
+    assert_eq!(assembly.chunks, [
+        Chunk { addr: 0x10, bytes: vec![1, b'A', b'B', b'C', 0, 0, 0xEA, 0xEA] },
+        Chunk { addr: 0x20, bytes: vec![0x34, 0x12] },
+    ]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("  foo"), AsmErrorKind::UnknownMnemonic("FOO".into()));
+    assert_eq!(error("  .macro"), AsmErrorKind::UnknownDirective(".macro".into()));
+    assert_eq!(error("  lda missing"), AsmErrorKind::UndefinedSymbol("missing".into()));
+    assert_eq!(error("a: nop\na: nop"), AsmErrorKind::DuplicateSymbol("a".into()));
+    assert_eq!(error("  lda #$100"), AsmErrorKind::OutOfRange(0x100));
+    assert_eq!(error("  stx $1234,x"), AsmErrorKind::InvalidAddressingMode("STX".into()));
+    assert_eq!(error("  lda ($1234),y"), AsmErrorKind::OutOfRange(0x1234));
+    assert_eq!(error("  bne * + 200"), AsmErrorKind::BranchOutOfRange(198));
+    assert_eq!(error("  .if 1"), AsmErrorKind::Syntax("missing .endif"));
+
+    let error = assemble(CpuVariant::Nmos6502, "  nop\n  lda (").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn runs_assembled_code() {
+    let assembly = assemble(CpuVariant::Nmos6502, "
+        .org $0200
+        ldx #0
+    loop:
+        txa
+        sta $10,x
+        inx
+        cpx #4
+        bne loop
+    done:
+        jmp done
+    ").unwrap();
+    let mut mem = Memory::filled(0);
+    assembly.write_to(&mut mem);
+    let mut cpu = Cpu6502::new();
+    cpu.pc = 0x0200;
+    while cpu.pc != assembly.symbol("done").unwrap() {
+        cpu.step(&mut mem).unwrap();
+    }
+    assert_eq!(mem.0[0x10..0x14], [0, 1, 2, 3]);
+}
@@ -0,0 +1,151 @@
+use pones_6502::{Cpu6502, CpuVariant};
+
+mod common;
+use common::Memory;
+
+// Loads the program at $0200 and runs it on a 65C02 for the given number of instructions.
+fn run(program: &[u8], steps: usize) -> (Cpu6502, Memory) {
+    common::run(CpuVariant::Wdc65C02, program, steps)
+}
+
+#[test]
+fn new_instructions() {
+    let (cpu, mem) = run(&[
+        0xA9, 0x0F, // LDA #$0F
+        0xA2, 0x34, // LDX #$34
+        0xDA,       // PHX
+        0x7A,       // PLY
+        0x64, 0x10, // STZ $10
+        0x04, 0x10, // TSB $10
+        0x1A,       // INC A
+        0x14, 0x10, // TRB $10
+        0x80, 0x01, // BRA +1
+        0xEA,       // NOP
+        0xF7, 0x11, // SMB7 $11
+    ], 10);
+    assert_eq!(cpu.reg.y, 0x34);
+    assert_eq!(cpu.reg.a, 0x10);
+    // None of A's bits were set for TRB to clear.
+    assert_eq!(mem.0[0x10], 0x0F);
+    assert!(cpu.reg.zero);
+    assert_eq!(mem.0[0x11], 0x80);
+    assert_eq!(cpu.pc, 0x0212);
+}
+
+#[test]
+fn new_addressing_modes() {
+    let mut program = vec![
+        0xB2, 0x20,       // LDA ($20)
+        0xA2, 0x02,       // LDX #$02
+        0x7C, 0x00, 0x03, // JMP ($0300,X)
+    ];
+    program.resize(0x100, 0);
+    program.extend([0x00, 0x00, 0x00, 0x10]);
+    let (mut cpu, mut mem) = run(&program, 0);
+    mem.0[0x20] = 0x00;
+    mem.0[0x21] = 0x04;
+    mem.0[0x0400] = 0x5A;
+    for _ in 0..3 {
+        cpu.step(&mut mem).unwrap();
+    }
+    assert_eq!(cpu.reg.a, 0x5A);
+    assert_eq!(cpu.pc, 0x1000);
+}
+
+#[test]
+fn indirect_jump_crosses_pages() {
+    let (mut cpu, mut mem) = run(&[0x6C, 0xFF, 0x02], 0);
+    mem.0[0x02FF] = 0x00;
+    mem.0[0x0300] = 0x40;
+    mem.0[0x0200] = 0x6C;
+    let cycles = cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x4000);
+    assert_eq!(cycles, 6);
+}
+
+#[test]
+fn bit_branches() {
+    let (mut cpu, mut mem) = run(&[
+        0x0F, 0x10, 0x02, // BBR0 $10,+2
+        0x8F, 0x10, 0x01, // BBS0 $10,+1
+        0x00,
+        0xEA,             // NOP
+    ], 0);
+    mem.0[0x10] = 0x01;
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0203);
+    let cycles = cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0207);
+    assert_eq!(cycles, 6);
+}
+
+#[test]
+fn decimal_flags() {
+    // SED; CLC; LDA #$99; ADC #$01
+    let (cpu, _) = run(&[0xF8, 0x18, 0xA9, 0x99, 0x69, 0x01], 4);
+    assert_eq!(cpu.reg.a, 0x00);
+    assert!(cpu.reg.carry);
+    // Unlike the NMOS 6502, Z reflects the decimal result.
+    assert!(cpu.reg.zero);
+    assert!(!cpu.reg.negative);
+
+    // SED; SEC; LDA #$00; SBC #$01
+    let (cpu, _) = run(&[0xF8, 0x38, 0xA9, 0x00, 0xE9, 0x01], 4);
+    assert_eq!(cpu.reg.a, 0x99);
+    assert!(!cpu.reg.carry);
+    assert!(cpu.reg.negative);
+}
+
+#[test]
+fn decimal_takes_an_extra_cycle() {
+    let (mut cpu, mut mem) = run(&[0xF8, 0x69, 0x01], 1);
+    assert_eq!(cpu.step(&mut mem).unwrap(), 3);
+}
+
+#[test]
+fn interrupts_clear_decimal() {
+    // SED; BRK
+    let (mut cpu, mut mem) = run(&[0xF8, 0x00], 0);
+    mem.0[0xFFFE] = 0x00;
+    mem.0[0xFFFF] = 0x03;
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0300);
+    assert!(!cpu.reg.decimal);
+    assert!(cpu.reg.interrupt_disable);
+}
+
+#[test]
+fn wait_for_interrupt() {
+    // SEI; WAI; INX
+    let (mut cpu, mut mem) = run(&[0x78, 0xCB, 0xE8], 2);
+    assert!(cpu.is_waiting());
+    for _ in 0..3 {
+        assert_eq!(cpu.step(&mut mem).unwrap(), 1);
+    }
+    assert_eq!(cpu.pc, 0x0202);
+
+    // With IRQs disabled, the IRQ only wakes the CPU up.
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert!(!cpu.is_waiting());
+    assert_eq!(cpu.reg.x, 1);
+}
+
+#[test]
+fn stop() {
+    let (mut cpu, mut mem) = run(&[0xDB], 0);
+    assert!(cpu.step(&mut mem).is_err());
+    assert!(cpu.is_halted());
+}
+
+#[test]
+fn undefined_opcodes_are_nops() {
+    // Single byte NOP; two byte NOP #$E8; INX
+    let (mut cpu, mut mem) = run(&[0x03, 0x02, 0xE8, 0xE8], 0);
+    assert_eq!(cpu.step(&mut mem).unwrap(), 1);
+    assert_eq!(cpu.step(&mut mem).unwrap(), 2);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.reg.x, 1);
+    assert_eq!(cpu.pc, 0x0204);
+}
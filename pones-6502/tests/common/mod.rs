@@ -0,0 +1,90 @@
+//! Helpers shared by the integration tests. Each test binary only uses some of them.
+#![allow(dead_code)]
+
+use pones_6502::{Bus, Cpu6502, CpuVariant};
+
+/// Where [`setup`] loads programs.
+pub const PROGRAM_START: u16 = 0x0200;
+
+/// 64 KB of RAM covering the whole address space.
+pub struct Memory(pub Box<[u8; 65536]>);
+
+impl Memory {
+    pub fn filled(value: u8) -> Self {
+        Self(Box::new([value; 65536]))
+    }
+
+    /// Copy `bytes` into memory, starting at `addr`.
+    pub fn load(&mut self, addr: u16, bytes: &[u8]) {
+        self.0[addr as usize..][..bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.0[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.0[addr as usize] = value;
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.0[addr as usize])
+    }
+}
+
+/// A bus access, as recorded by [`RecordingBus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+/// 64 KB of zeroed RAM that records every access made to it, in order.
+pub struct RecordingBus {
+    pub mem: Box<[u8; 65536]>,
+    pub accesses: Vec<Access>,
+}
+
+impl RecordingBus {
+    pub fn new() -> Self {
+        Self {
+            mem: Box::new([0; 65536]),
+            accesses: Vec::new(),
+        }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.mem[addr as usize];
+        self.accesses.push(Access::Read(addr, value));
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.accesses.push(Access::Write(addr, value));
+        self.mem[addr as usize] = value;
+    }
+}
+
+/// A CPU of `variant` about to run `program`, which is loaded at [`PROGRAM_START`] into
+/// otherwise zeroed memory. The stack pointer starts at $FD, as it does after a reset.
+pub fn setup(variant: CpuVariant, program: &[u8]) -> (Cpu6502, Memory) {
+    let mut mem = Memory::filled(0);
+    mem.load(PROGRAM_START, program);
+    let mut cpu = Cpu6502::with_variant(variant);
+    cpu.pc = PROGRAM_START;
+    cpu.sp = 0xFD;
+    (cpu, mem)
+}
+
+/// Like [`setup`], then run `steps` instructions.
+pub fn run(variant: CpuVariant, program: &[u8], steps: usize) -> (Cpu6502, Memory) {
+    let (mut cpu, mut mem) = setup(variant, program);
+    for _ in 0..steps {
+        cpu.step(&mut mem).expect("cpu halted");
+    }
+    (cpu, mem)
+}
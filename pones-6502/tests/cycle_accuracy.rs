@@ -0,0 +1,270 @@
+use pones_6502::{Cpu6502, Bus, ReadKind};
+
+mod common;
+use common::{Access, RecordingBus, PROGRAM_START};
+use Access::*;
+
+fn run(program: &[u8], setup: impl FnOnce(&mut Cpu6502, &mut [u8; 65536])) -> (u32, Vec<Access>) {
+    let mut bus = RecordingBus::new();
+    let start = PROGRAM_START as usize;
+    bus.mem[start..start + program.len()].copy_from_slice(program);
+    let mut cpu = Cpu6502::new();
+    cpu.cycle_accurate = true;
+    cpu.pc = PROGRAM_START;
+    cpu.sp = 0xFD;
+    setup(&mut cpu, &mut bus.mem);
+    let cycles = cpu.step(&mut bus).expect("cpu halted");
+    assert_eq!(cycles as usize, bus.accesses.len(), "cycle count doesn't match bus accesses");
+    (cycles, bus.accesses)
+}
+
+#[test]
+fn implied() {
+    let (_, accesses) = run(&[0xE8, 0xAA], |_, _| {}); // INX
+    assert_eq!(accesses, [Read(0x0200, 0xE8), Read(0x0201, 0xAA)]);
+}
+
+#[test]
+fn absolute_indexed_read() {
+    // LDA $1280,Y without a page cross
+    let (_, accesses) = run(&[0xB9, 0x80, 0x12], |cpu, mem| {
+        cpu.reg.y = 0x10;
+        mem[0x1290] = 0x55;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0xB9),
+        Read(0x0201, 0x80),
+        Read(0x0202, 0x12),
+        Read(0x1290, 0x55),
+    ]);
+
+    // LDA $12F0,Y with a page cross
+    let (_, accesses) = run(&[0xB9, 0xF0, 0x12], |cpu, mem| {
+        cpu.reg.y = 0x20;
+        mem[0x1210] = 0x11;
+        mem[0x1310] = 0x55;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0xB9),
+        Read(0x0201, 0xF0),
+        Read(0x0202, 0x12),
+        Read(0x1210, 0x11),
+        Read(0x1310, 0x55),
+    ]);
+}
+
+#[test]
+fn absolute_indexed_write() {
+    // STA $2000,X
+    let (_, accesses) = run(&[0x9D, 0x00, 0x20], |cpu, _| {
+        cpu.reg.a = 0x42;
+        cpu.reg.x = 0x07;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0x9D),
+        Read(0x0201, 0x00),
+        Read(0x0202, 0x20),
+        Read(0x2007, 0x00),
+        Write(0x2007, 0x42),
+    ]);
+}
+
+#[test]
+fn read_modify_write() {
+    // INC $1234,X
+    let (_, accesses) = run(&[0xFE, 0x34, 0x12], |cpu, mem| {
+        cpu.reg.x = 0xFF;
+        mem[0x1233] = 0x99;
+        mem[0x1333] = 0x7F;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0xFE),
+        Read(0x0201, 0x34),
+        Read(0x0202, 0x12),
+        Read(0x1233, 0x99),
+        Read(0x1333, 0x7F),
+        Write(0x1333, 0x7F),
+        Write(0x1333, 0x80),
+    ]);
+}
+
+#[test]
+fn indirect_indexed() {
+    // STA ($80),Y
+    let (_, accesses) = run(&[0x91, 0x80], |cpu, mem| {
+        cpu.reg.a = 0x42;
+        cpu.reg.y = 0x01;
+        mem[0x0080] = 0xFF;
+        mem[0x0081] = 0x03;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0x91),
+        Read(0x0201, 0x80),
+        Read(0x0080, 0xFF),
+        Read(0x0081, 0x03),
+        Read(0x0300, 0x00),
+        Write(0x0400, 0x42),
+    ]);
+}
+
+#[test]
+fn indexed_indirect() {
+    // LDA ($FE,X)
+    let (_, accesses) = run(&[0xA1, 0xFE], |cpu, mem| {
+        cpu.reg.x = 0x01;
+        mem[0x00FF] = 0x34;
+        mem[0x0000] = 0x12;
+        mem[0x1234] = 0x55;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0xA1),
+        Read(0x0201, 0xFE),
+        Read(0x00FE, 0x00),
+        Read(0x00FF, 0x34),
+        Read(0x0000, 0x12),
+        Read(0x1234, 0x55),
+    ]);
+}
+
+#[test]
+fn branch() {
+    // BNE -4, not taken
+    let (cycles, _) = run(&[0xD0, 0xFC], |cpu, _| cpu.reg.zero = true);
+    assert_eq!(cycles, 2);
+
+    // BNE -4, taken across a page boundary
+    let (_, accesses) = run(&[0xD0, 0xFC], |_, _| {});
+    assert_eq!(accesses, [
+        Read(0x0200, 0xD0),
+        Read(0x0201, 0xFC),
+        Read(0x0202, 0x00),
+        Read(0x02FE, 0x00),
+    ]);
+}
+
+#[test]
+fn subroutines() {
+    // JSR $1234
+    let (_, accesses) = run(&[0x20, 0x34, 0x12], |_, _| {});
+    assert_eq!(accesses, [
+        Read(0x0200, 0x20),
+        Read(0x0201, 0x34),
+        Read(0x01FD, 0x00),
+        Write(0x01FD, 0x02),
+        Write(0x01FC, 0x02),
+        Read(0x0202, 0x12),
+    ]);
+
+    // RTS
+    let (_, accesses) = run(&[0x60], |cpu, mem| {
+        cpu.sp = 0xFB;
+        mem[0x01FC] = 0x02;
+        mem[0x01FD] = 0x10;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0x60),
+        Read(0x0201, 0x00),
+        Read(0x01FB, 0x00),
+        Read(0x01FC, 0x02),
+        Read(0x01FD, 0x10),
+        Read(0x1002, 0x00),
+    ]);
+}
+
+#[test]
+fn brk() {
+    let (_, accesses) = run(&[0x00, 0xEA], |cpu, mem| {
+        cpu.reg.carry = true;
+        mem[0xFFFE] = 0x00;
+        mem[0xFFFF] = 0x80;
+    });
+    assert_eq!(accesses, [
+        Read(0x0200, 0x00),
+        Read(0x0201, 0xEA),
+        Write(0x01FD, 0x02),
+        Write(0x01FC, 0x02),
+        Write(0x01FB, 0x31),
+        Read(0xFFFE, 0x00),
+        Read(0xFFFF, 0x80),
+    ]);
+}
+
+#[test]
+fn dummy_accesses_disabled() {
+    let mut bus = RecordingBus {
+        mem: Box::new([0; 65536]),
+        accesses: Vec::new(),
+    };
+    bus.mem[0x0200..0x0203].copy_from_slice(&[0xFE, 0x34, 0x12]); // INC $1234,X
+    let mut cpu = Cpu6502::new();
+    cpu.pc = PROGRAM_START;
+    cpu.reg.x = 0xFF;
+    let cycles = cpu.step(&mut bus).expect("cpu halted");
+    assert_eq!(cycles, 7);
+    assert_eq!(bus.accesses, [
+        Read(0x0200, 0xFE),
+        Read(0x0201, 0x34),
+        Read(0x0202, 0x12),
+        Read(0x1333, 0x00),
+        Write(0x1333, 0x01),
+    ]);
+}
+
+#[test]
+fn read_kinds() {
+    struct KindBus {
+        mem: Box<[u8; 65536]>,
+        kinds: Vec<(u16, ReadKind)>,
+    }
+
+    impl Bus for KindBus {
+        fn read(&mut self, _addr: u16) -> u8 {
+            unreachable!("the CPU reads through read_as");
+        }
+
+        fn read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+            self.kinds.push((addr, kind));
+            self.mem[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.mem[addr as usize] = value;
+        }
+    }
+
+    let mut bus = KindBus {
+        mem: Box::new([0; 65536]),
+        kinds: Vec::new(),
+    };
+    bus.mem[0x0200..0x0207].copy_from_slice(&[
+        0xA9, 0x12, // LDA #$12
+        0xB1, 0x10, // LDA ($10),Y
+        0x20, 0x34, 0x12, // JSR $1234
+    ]);
+    bus.mem[0x10] = 0xF0;
+    bus.mem[0x11] = 0x12;
+    let mut cpu = Cpu6502::new();
+    cpu.cycle_accurate = true;
+    cpu.pc = PROGRAM_START;
+    cpu.sp = 0xFD;
+    cpu.reg.y = 0x20;
+    for _ in 0..3 {
+        cpu.step(&mut bus).expect("cpu halted");
+    }
+
+    use ReadKind::*;
+    assert_eq!(bus.kinds, [
+        (0x0200, Opcode),
+        (0x0201, Operand),
+        (0x0202, Opcode),
+        (0x0203, Operand),
+        (0x0010, Data),
+        (0x0011, Data),
+        (0x1210, Dummy),
+        (0x1310, Data),
+        (0x0204, Opcode),
+        (0x0205, Operand),
+        (0x01FD, Dummy),
+        (0x0206, Operand),
+    ]);
+}
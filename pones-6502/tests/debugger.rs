@@ -0,0 +1,166 @@
+use pones_6502::{AccessKind, Bus, Cpu6502, Debugger, StopReason, Watchpoint};
+
+mod common;
+use common::Memory;
+
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x0200, &[
+        0xA2, 0x00,       // LDX #$00
+        0x20, 0x10, 0x02, // JSR $0210
+        0xE8,             // INX
+        0xE0, 0x03,       // CPX #$03
+        0xD0, 0xF8,       // BNE $0202
+        0x4C, 0x0A, 0x02, // JMP $020A
+    ]),
+    (0x0210, &[
+        0x8E, 0x00, 0x03, // STX $0300
+        0xAD, 0x01, 0x03, // LDA $0301
+        0x20, 0x20, 0x02, // JSR $0220
+        0x60,             // RTS
+    ]),
+    (0x0220, &[
+        0xEA,             // NOP
+        0x60,             // RTS
+    ]),
+];
+
+fn setup() -> (Cpu6502, Memory, Debugger) {
+    let mut mem = Memory::filled(0);
+    for &(addr, bytes) in PROGRAM {
+        mem.load(addr, bytes);
+    }
+    let mut cpu = Cpu6502::new();
+    cpu.pc = 0x0200;
+    cpu.sp = 0xFD;
+    (cpu, mem, Debugger::new())
+}
+
+#[test]
+fn breakpoints() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    debugger.add_breakpoint(0x0205);
+    for x in 0..3 {
+        assert_eq!(debugger.run(&mut cpu, &mut mem), StopReason::Breakpoint(0x0205));
+        assert_eq!(cpu.reg.x, x);
+    }
+    assert!(debugger.remove_breakpoint(0x0205));
+    assert!(!debugger.remove_breakpoint(0x0205));
+}
+
+#[test]
+fn conditional_breakpoints() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    debugger.add_conditional_breakpoint(0x0210, |cpu| cpu.reg.x == 1);
+    assert_eq!(debugger.run(&mut cpu, &mut mem), StopReason::Breakpoint(0x0210));
+    assert_eq!(cpu.reg.x, 1);
+}
+
+#[test]
+fn watchpoints() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    let write = Watchpoint { range: 0x0300..=0x0300, read: false, write: true, execute: false };
+    debugger.add_watchpoint(write.clone());
+    debugger.add_watchpoint(Watchpoint { range: 0x0301..=0x03FF, read: true, write: false, execute: false });
+
+    let stop = debugger.run(&mut cpu, &mut mem);
+    assert_eq!(stop, StopReason::Watchpoint { addr: 0x0300, kind: AccessKind::Write });
+    assert_eq!(cpu.pc, 0x0213);
+    let stop = debugger.run(&mut cpu, &mut mem);
+    assert_eq!(stop, StopReason::Watchpoint { addr: 0x0301, kind: AccessKind::Read });
+    assert_eq!(cpu.pc, 0x0216);
+
+    assert!(debugger.remove_watchpoint(&write));
+    debugger.add_watchpoint(Watchpoint { range: 0x0220..=0x0220, read: false, write: false, execute: true });
+    let stop = debugger.run(&mut cpu, &mut mem);
+    assert_eq!(stop, StopReason::Watchpoint { addr: 0x0220, kind: AccessKind::Execute });
+    assert_eq!(cpu.pc, 0x0220);
+}
+
+#[test]
+fn read_watchpoints_ignore_code() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    debugger.add_watchpoint(Watchpoint { range: 0x0200..=0x02FF, read: true, write: false, execute: false });
+    debugger.add_breakpoint(0x0205);
+    assert_eq!(debugger.run(&mut cpu, &mut mem), StopReason::Breakpoint(0x0205));
+}
+
+#[test]
+fn stepping() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    assert_eq!(debugger.step(&mut cpu, &mut mem), StopReason::Done);
+    assert_eq!(cpu.pc, 0x0202);
+    assert_eq!(debugger.step_over(&mut cpu, &mut mem), StopReason::Done);
+    assert_eq!((cpu.pc, cpu.sp), (0x0205, 0xFD));
+
+    // Step into the first iteration's call, then out of the nested calls one at a time.
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    assert_eq!(cpu.pc, 0x0210);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    assert_eq!(cpu.pc, 0x0220);
+    assert_eq!(debugger.step_out(&mut cpu, &mut mem), StopReason::Done);
+    assert_eq!(cpu.pc, 0x0219);
+    assert_eq!(debugger.step_out(&mut cpu, &mut mem), StopReason::Done);
+    assert_eq!((cpu.pc, cpu.sp), (0x0205, 0xFD));
+
+    // Breakpoints still stop a step over.
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.step(&mut cpu, &mut mem);
+    debugger.add_breakpoint(0x0220);
+    assert_eq!(debugger.step_over(&mut cpu, &mut mem), StopReason::Breakpoint(0x0220));
+}
+
+#[test]
+fn step_out_with_wrapping_stack() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    debugger.step(&mut cpu, &mut mem);
+    cpu.sp = 0x00;
+    debugger.step(&mut cpu, &mut mem);
+    assert_eq!((cpu.pc, cpu.sp), (0x0210, 0xFE));
+    assert_eq!(debugger.step_out(&mut cpu, &mut mem), StopReason::Done);
+    assert_eq!((cpu.pc, cpu.sp), (0x0205, 0x00));
+}
+
+#[test]
+fn step_over_without_peek() {
+    struct NoPeek(Memory);
+
+    impl Bus for NoPeek {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.0.read(addr)
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.0.write(addr, value);
+        }
+    }
+
+    let (mut cpu, mem, mut debugger) = setup();
+    let mut bus = NoPeek(mem);
+    debugger.step(&mut cpu, &mut bus);
+    assert_eq!(debugger.step_over(&mut cpu, &mut bus), StopReason::Done);
+    assert_eq!((cpu.pc, cpu.sp), (0x0205, 0xFD));
+    assert_eq!(debugger.step_over(&mut cpu, &mut bus), StopReason::Done);
+    assert_eq!(cpu.pc, 0x0206);
+}
+
+#[test]
+fn run_until_cycle() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    assert_eq!(debugger.run_until_cycle(&mut cpu, &mut mem, 100), StopReason::Done);
+    assert!(cpu.cycles >= 100 && cpu.cycles < 107);
+    assert_eq!(debugger.run_until_cycle(&mut cpu, &mut mem, 50), StopReason::Done);
+}
+
+#[test]
+fn halted() {
+    let (mut cpu, mut mem, mut debugger) = setup();
+    mem.0[0x0220] = 0x02;
+    let stop = debugger.run(&mut cpu, &mut mem);
+    assert!(matches!(stop, StopReason::Halted(halted) if halted.pc == 0x0220));
+}
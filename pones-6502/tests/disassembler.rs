@@ -0,0 +1,82 @@
+use pones_6502::{disassemble, trace_line_with_symbols, AddrMode, Bus, Cpu6502, CpuVariant, SymbolTable};
+
+mod common;
+use common::Memory;
+
+fn disassemble_bytes(addr: u16, bytes: &[u8]) -> String {
+    let mut mem = Memory::filled(0);
+    mem.load(addr, bytes);
+    let instr = disassemble(CpuVariant::Nmos6502, &mem, addr).unwrap();
+    assert_eq!(instr.bytes(), bytes);
+    instr.to_string()
+}
+
+#[test]
+fn addressing_modes() {
+    assert_eq!(disassemble_bytes(0x0200, &[0xEA]), "NOP");
+    assert_eq!(disassemble_bytes(0x0200, &[0x0A]), "ASL A");
+    assert_eq!(disassemble_bytes(0x0200, &[0xA9, 0x12]), "LDA #$12");
+    assert_eq!(disassemble_bytes(0x0200, &[0xA5, 0x12]), "LDA $12");
+    assert_eq!(disassemble_bytes(0x0200, &[0xB5, 0x12]), "LDA $12,X");
+    assert_eq!(disassemble_bytes(0x0200, &[0xB6, 0x12]), "LDX $12,Y");
+    assert_eq!(disassemble_bytes(0x0200, &[0xAD, 0x34, 0x12]), "LDA $1234");
+    assert_eq!(disassemble_bytes(0x0200, &[0xBD, 0x34, 0x12]), "LDA $1234,X");
+    assert_eq!(disassemble_bytes(0x0200, &[0xB9, 0x34, 0x12]), "LDA $1234,Y");
+    assert_eq!(disassemble_bytes(0x0200, &[0x6C, 0x34, 0x12]), "JMP ($1234)");
+    assert_eq!(disassemble_bytes(0x0200, &[0xA1, 0x12]), "LDA ($12,X)");
+    assert_eq!(disassemble_bytes(0x0200, &[0xB1, 0x12]), "LDA ($12),Y");
+    assert_eq!(disassemble_bytes(0x0200, &[0xD0, 0xFC]), "BNE $01FE");
+    assert_eq!(disassemble_bytes(0x0200, &[0x10, 0x10]), "BPL $0212");
+}
+
+#[test]
+fn illegal_opcodes() {
+    assert_eq!(disassemble_bytes(0x0200, &[0x04, 0x12]), "*NOP $12");
+    assert_eq!(disassemble_bytes(0x0200, &[0xA7, 0x12]), "*LAX $12");
+    assert_eq!(disassemble_bytes(0x0200, &[0x02]), "*STP");
+
+    let mut mem = Memory::filled(0);
+    mem.0[0x0200..0x0203].copy_from_slice(&[0x9F, 0x34, 0x12]);
+    let instr = disassemble(CpuVariant::Nmos6502, &mem, 0x0200).unwrap();
+    assert!(!instr.official);
+    assert_eq!(instr.mnemonic, "AHX");
+    assert_eq!(instr.mode, AddrMode::AbsoluteY);
+    assert_eq!(instr.operand_bytes(), [0x34, 0x12]);
+    assert_eq!(instr.len, 3);
+}
+
+#[test]
+fn unpeekable() {
+    struct IoBus;
+
+    impl Bus for IoBus {
+        fn read(&mut self, _addr: u16) -> u8 {
+            0xEA
+        }
+
+        fn write(&mut self, _addr: u16, _value: u8) {}
+    }
+
+    assert_eq!(disassemble(CpuVariant::Nmos6502, &IoBus, 0x0200), None);
+}
+
+#[test]
+fn symbols() {
+    let symbols = SymbolTable::parse_nl("$0010#ptr#\n$0200#start#\n$1230#table#\n").unwrap();
+    let mut mem = Memory::filled(0);
+    mem.0[0x0200..0x0207].copy_from_slice(&[
+        0xBD, 0x34, 0x12, // LDA $1234,X
+        0xB1, 0x11,       // LDA ($11),Y
+        0xD0, 0xF9,       // BNE $0200
+    ]);
+    let disassembly: Vec<String> = [0x0200, 0x0203, 0x0205]
+        .into_iter()
+        .map(|addr| disassemble(CpuVariant::Nmos6502, &mem, addr).unwrap().with_symbols(&symbols).to_string())
+        .collect();
+    assert_eq!(disassembly, ["LDA table+4,X", "LDA (ptr+1),Y", "BNE start"].map(String::from));
+
+    let mut cpu = Cpu6502::new();
+    cpu.pc = 0x0200;
+    let line = trace_line_with_symbols(&cpu, &mem, None, &symbols);
+    assert!(line.starts_with("0200  BD 34 12  LDA table+4,X @ 1234 = 00"), "{}", line);
+}
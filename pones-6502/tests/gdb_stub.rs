@@ -0,0 +1,113 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::spawn;
+
+use pones_6502::{Cpu6502, GdbStub};
+
+mod common;
+use common::Memory;
+
+struct Client(TcpStream);
+
+impl Client {
+    fn send(&mut self, packet: &str) {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.0, "${}#{:02x}", packet, checksum).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+    }
+
+    fn receive(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut packet = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => packet.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.0.read_exact(&mut checksum).unwrap();
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(packet.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)), checksum);
+        self.0.write_all(b"+").unwrap();
+        String::from_utf8(packet).unwrap()
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.receive()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.0.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut cpu = Cpu6502::new();
+        let mut mem = Memory::filled(0);
+        GdbStub::new(stream).run(&mut cpu, &mut mem).unwrap();
+        (cpu, mem)
+    });
+    let mut client = Client(TcpStream::connect(addr).unwrap());
+
+    assert!(client.request("qSupported:swbreak+").contains("PacketSize="));
+    assert_eq!(client.request("?"), "S05");
+
+    // A, X, Y, SP, PC (little endian), P
+    assert_eq!(client.request("G010203fd000224"), "OK");
+    assert_eq!(client.request("g"), "010203fd000224");
+    assert_eq!(client.request("p4"), "0002");
+    assert_eq!(client.request("P1=00"), "OK");
+    assert_eq!(client.request("p9"), "E01");
+
+    // LDX #$00; INX; STX $0300; JMP $0202
+    assert_eq!(client.request("M200,9:a200e88e00034c0202"), "OK");
+    assert_eq!(client.request("m200,9"), "a200e88e00034c0202");
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p4"), "0202");
+
+    assert_eq!(client.request("Z0,203,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("g"), "010103fd030224");
+    assert_eq!(client.request("z0,203,1"), "OK");
+
+    assert_eq!(client.request("Z2,300,1"), "OK");
+    assert_eq!(client.request("c"), "T05watch:300;");
+    assert_eq!(client.request("m300,1"), "01");
+    assert_eq!(client.request("z2,300,1"), "OK");
+    assert_eq!(client.request("Z2,fff0,20"), "E01");
+    assert_eq!(client.request("Z2,fff0,10"), "OK");
+    assert_eq!(client.request("z2,fff0,10"), "OK");
+    assert_eq!(client.request("Z2,0,10000"), "OK");
+    assert_eq!(client.request("z2,0,10000"), "OK");
+    assert_eq!(client.request("Z2,1,10000"), "E01");
+
+    assert_eq!(client.request("Z4,300,1"), "OK");
+    assert_eq!(client.request("c"), "T05awatch:300;");
+    assert_eq!(client.request("z4,300,1"), "OK");
+
+    // Resuming at an address.
+    assert_eq!(client.request("s200"), "S05");
+    assert_eq!(client.request("p4"), "0202");
+    assert_eq!(client.request("p1"), "00");
+    assert_eq!(client.request("s20x"), "E01");
+
+    // With nothing left to stop at, only an interrupt from the client stops the CPU.
+    client.send("c");
+    client.0.write_all(&[0x03]).unwrap();
+    assert_eq!(client.receive(), "S02");
+
+    client.send("k");
+    let (cpu, mem) = server.join().unwrap();
+    assert!(cpu.pc >= 0x0202 && cpu.pc <= 0x0206);
+    assert_eq!(mem.0[0x0300], cpu.reg.x.wrapping_sub((cpu.pc == 0x0203) as u8));
+}
@@ -0,0 +1,236 @@
+//! The interrupt lines and their timing. The cpu_interrupts_v2 test ROMs aren't in the
+//! tree and haven't been run against the core. These tests are written by hand from the
+//! behaviour those ROMs check.
+
+use pones_6502::{Bus, Cpu6502};
+
+mod common;
+use common::{Memory, PROGRAM_START};
+
+const NMI_HANDLER: u16 = 0x0300;
+const IRQ_HANDLER: u16 = 0x0400;
+
+// Everything is NOPs except for `program` at $0200.
+fn setup(program: &[u8]) -> (Cpu6502, Memory) {
+    let mut mem = Memory::filled(0xEA);
+    mem.load(PROGRAM_START, program);
+    mem.load(0xFFFA, &NMI_HANDLER.to_le_bytes());
+    mem.load(0xFFFE, &IRQ_HANDLER.to_le_bytes());
+    let mut cpu = Cpu6502::new();
+    cpu.pc = PROGRAM_START;
+    cpu.sp = 0xFF;
+    (cpu, mem)
+}
+
+fn pushed_return_addr(cpu: &Cpu6502, mem: &Memory) -> u16 {
+    let sp = 0x0100 + cpu.sp as usize;
+    u16::from_le_bytes([mem.0[sp + 2], mem.0[sp + 3]])
+}
+
+#[test]
+fn irq_line_is_level_sensitive() {
+    let (mut cpu, mut mem) = setup(&[]);
+    cpu.set_irq(1 << 0, true);
+    cpu.set_irq(1 << 1, true);
+    assert_eq!(cpu.step(&mut mem), Ok(7));
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), 0x0200);
+    assert_eq!(mem.0[0x01FD] & 0x30, 0x20);
+
+    // Masked by the interrupt disable flag set by the interrupt.
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER + 2);
+
+    cpu.set_irq(1 << 0, false);
+    assert_eq!(cpu.irq_sources(), 1 << 1);
+    cpu.reg.interrupt_disable = false;
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+
+    cpu.set_irq(1 << 1, false);
+    cpu.reg.interrupt_disable = false;
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER + 2);
+}
+
+#[test]
+fn nmi_line_is_edge_triggered() {
+    let (mut cpu, mut mem) = setup(&[]);
+    cpu.reg.interrupt_disable = true;
+    cpu.set_nmi(true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), 0x0200);
+
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER + 2);
+
+    cpu.set_nmi(false);
+    cpu.set_nmi(true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), NMI_HANDLER + 2);
+}
+
+#[test]
+fn handler_runs_one_instruction_before_another_interrupt() {
+    let (mut cpu, mut mem) = setup(&[]);
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    cpu.set_nmi(true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER + 1);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER);
+}
+
+#[test]
+fn cli_and_sei_take_effect_after_the_next_instruction() {
+    // CLI; NOP
+    let (mut cpu, mut mem) = setup(&[0x58, 0xEA]);
+    cpu.reg.interrupt_disable = true;
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0202);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), 0x0202);
+
+    // SEI; NOP
+    let (mut cpu, mut mem) = setup(&[0x78, 0xEA]);
+    cpu.step(&mut mem).unwrap();
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), 0x0201);
+    // The pushed status has the flag set by SEI.
+    assert_eq!(mem.0[0x01FD] & 0x04, 0x04);
+}
+
+#[test]
+fn plp_takes_effect_after_the_next_instruction() {
+    // PLP; NOP
+    let (mut cpu, mut mem) = setup(&[0x28, 0xEA]);
+    cpu.reg.interrupt_disable = true;
+    cpu.sp = 0xFE;
+    mem.0[0x01FF] = 0x20;
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert!(!cpu.reg.interrupt_disable);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0202);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+}
+
+#[test]
+fn taken_branch_delays_interrupts() {
+    // BNE $0202; NOP
+    let (mut cpu, mut mem) = setup(&[0xD0, 0x00, 0xEA]);
+    cpu.step(&mut mem).unwrap();
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0203);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+
+    // Branches that cross a page poll again in their last cycle.
+    let (mut cpu, mut mem) = setup(&[]);
+    cpu.pc = 0x02FD;
+    mem.0[0x02FD..0x02FF].copy_from_slice(&[0xD0, 0x01]);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0300);
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+}
+
+#[test]
+fn nmi_hijacks_brk() {
+    // BNE $0202; BRK
+    let (mut cpu, mut mem) = setup(&[0xD0, 0x00, 0x00]);
+    cpu.step(&mut mem).unwrap();
+    // Delayed by the branch, so it's only detected during the BRK.
+    cpu.set_nmi(true);
+    assert_eq!(cpu.step(&mut mem), Ok(7));
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &mem), 0x0204);
+    assert_eq!(mem.0[0x01FD] & 0x10, 0x10);
+
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER + 2);
+}
+
+#[test]
+fn nmi_hijacks_irq() {
+    let (mut cpu, mut mem) = setup(&[]);
+    cpu.set_nmi(true);
+    assert_eq!(cpu.irq(&mut mem), 7);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(mem.0[0x01FD] & 0x10, 0x00);
+
+    cpu.step(&mut mem).unwrap();
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER + 2);
+}
+
+// Raises the NMI line once the CPU has made `nmi_after_writes` writes.
+struct NmiBus {
+    mem: Memory,
+    writes: usize,
+    nmi_after_writes: usize,
+}
+
+impl Bus for NmiBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.mem.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.writes += 1;
+        self.mem.write(addr, value);
+    }
+
+    fn nmi_line(&mut self) -> Option<bool> {
+        Some(self.writes >= self.nmi_after_writes)
+    }
+}
+
+fn nmi_bus(mem: Memory, nmi_after_writes: usize) -> NmiBus {
+    NmiBus { mem, writes: 0, nmi_after_writes }
+}
+
+#[test]
+fn nmi_during_brk_hijacks_it() {
+    // BRK, with the NMI raised by the first push.
+    let (mut cpu, mem) = setup(&[0x00]);
+    let mut bus = nmi_bus(mem, 1);
+    assert_eq!(cpu.step(&mut bus), Ok(7));
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &bus.mem), 0x0202);
+    assert_eq!(bus.mem.0[0x01FD] & 0x10, 0x10);
+
+    // Raised by the status push, it's too late to change the vector.
+    let (mut cpu, mem) = setup(&[0x00]);
+    let mut bus = nmi_bus(mem, 3);
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.pc, NMI_HANDLER);
+}
+
+#[test]
+fn nmi_during_irq_hijacks_it() {
+    let (mut cpu, mem) = setup(&[]);
+    let mut bus = nmi_bus(mem, 2);
+    cpu.set_irq(1 << 0, true);
+    assert_eq!(cpu.step(&mut bus), Ok(7));
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed_return_addr(&cpu, &bus.mem), 0x0200);
+    assert_eq!(bus.mem.0[0x01FD] & 0x10, 0x00);
+}
@@ -0,0 +1,81 @@
+use pones_6502::{AddrMode, CpuVariant, RegisterSet, CMOS_OPCODES, NMOS_OPCODES};
+
+mod common;
+use common::setup;
+
+// Runs the opcode with the given operand bytes and index registers, returning the cycles taken.
+fn cycles_taken(variant: CpuVariant, opcode: u8, operand: [u8; 2], index: u8) -> u32 {
+    let (mut cpu, mut mem) = setup(variant, &[opcode, operand[0], operand[1]]);
+    cpu.reg.x = index;
+    cpu.reg.y = index;
+    cpu.step(&mut mem).expect("cpu halted")
+}
+
+fn check_cycles(variant: CpuVariant) {
+    for (opcode, info) in variant.opcodes().iter().enumerate() {
+        let opcode = opcode as u8;
+        if info.mnemonic() == "STP" {
+            continue;
+        }
+
+        let cycles = cycles_taken(variant, opcode, [0x00, 0x00], 0);
+        if matches!(info.mode, AddrMode::Relative | AddrMode::ZeroPageRelative) {
+            // Taken with an offset of 0, so it can't cross a page.
+            assert!(cycles == info.cycles as u32 || cycles == info.cycles as u32 + 1, "{:02X}", opcode);
+            continue;
+        }
+        assert_eq!(cycles, info.cycles as u32, "cycles for {:02X} {}", opcode, info.mnemonic());
+
+        if matches!(info.mode, AddrMode::AbsoluteX | AddrMode::AbsoluteY) {
+            let cycles = cycles_taken(variant, opcode, [0xFF, 0x00], 1);
+            let expected = info.cycles as u32 + info.page_cross_penalty as u32;
+            assert_eq!(cycles, expected, "page cross cycles for {:02X} {}", opcode, info.mnemonic());
+        }
+    }
+}
+
+#[test]
+fn cycles_match_execution() {
+    check_cycles(CpuVariant::Nmos6502);
+}
+
+#[test]
+fn cmos_cycles_match_execution() {
+    check_cycles(CpuVariant::Wdc65C02);
+}
+
+#[test]
+fn table_contents() {
+    let lda = &NMOS_OPCODES[0xBD];
+    assert_eq!(lda.mnemonic(), "LDA");
+    assert_eq!(lda.mode, AddrMode::AbsoluteX);
+    assert_eq!(lda.len, 3);
+    assert!(lda.official && lda.page_cross_penalty);
+    assert!(lda.reads.contains(RegisterSet::X));
+    assert!(lda.writes.contains(RegisterSet::A | RegisterSet::NEGATIVE | RegisterSet::ZERO));
+
+    let rol = &NMOS_OPCODES[0x2A];
+    assert_eq!(rol.mode, AddrMode::Accumulator);
+    assert!(rol.reads.contains(RegisterSet::A | RegisterSet::CARRY));
+
+    let official = NMOS_OPCODES.iter().filter(|info| info.official).count();
+    assert_eq!(official, 151);
+}
+
+#[test]
+fn cmos_table_contents() {
+    let bbs = &CMOS_OPCODES[0xFF];
+    assert_eq!(bbs.mnemonic(), "BBS7");
+    assert_eq!(bbs.mode, AddrMode::ZeroPageRelative);
+    assert_eq!(bbs.len, 3);
+
+    let lda = &CMOS_OPCODES[0xB2];
+    assert_eq!(lda.mnemonic(), "LDA");
+    assert_eq!(lda.mode, AddrMode::ZeroPageIndirect);
+    assert_eq!(lda.cycles, 5);
+
+    assert_eq!(CMOS_OPCODES[0x6C].cycles, 6);
+    assert_eq!(CMOS_OPCODES[0x03].len, 1);
+    assert_eq!(CMOS_OPCODES[0x5C].cycles, 8);
+    assert!(CMOS_OPCODES.iter().all(|info| info.official || info.mnemonic() == "NOP"));
+}
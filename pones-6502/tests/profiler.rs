@@ -0,0 +1,127 @@
+use pones_6502::{assemble, Assembly, Cpu6502, CpuVariant, InstructionProfile, Profiler, RoutineProfile};
+
+mod common;
+use common::Memory;
+
+const PROGRAM: &str = "
+        .org $0200
+    start:
+        jsr sub
+        jsr sub
+        jsr outer
+    done:
+        jmp done
+    sub:
+        ldx #3
+    loop:
+        dex
+        bne loop
+        rts
+    outer:
+        jsr sub
+        rts
+    irq:
+        rti
+";
+
+fn setup() -> (Cpu6502, Memory, Assembly) {
+    let assembly = assemble(CpuVariant::Nmos6502, PROGRAM).unwrap();
+    let mut mem = Memory::filled(0);
+    assembly.write_to(&mut mem);
+    let irq = assembly.symbol("irq").unwrap();
+    mem.0[0xFFFE..].copy_from_slice(&irq.to_le_bytes());
+    let mut cpu = Cpu6502::new();
+    cpu.pc = 0x0200;
+    cpu.sp = 0xFD;
+    (cpu, mem, assembly)
+}
+
+fn run_until(profiler: &mut Profiler, cpu: &mut Cpu6502, mem: &mut Memory, addr: u16) {
+    while cpu.pc != addr {
+        profiler.step(cpu, mem).unwrap();
+    }
+}
+
+#[test]
+fn call_graph() {
+    let (mut cpu, mut mem, assembly) = setup();
+    let symbol = |name| assembly.symbol(name).unwrap();
+    let mut profiler = Profiler::new();
+    run_until(&mut profiler, &mut cpu, &mut mem, symbol("done"));
+
+    // DEX runs 3 times per call, and BNE takes 3 cycles when it branches.
+    assert_eq!(profiler.instruction(symbol("loop")), InstructionProfile { executions: 9, cycles: 18 });
+    assert_eq!(profiler.instruction(symbol("loop") + 1).cycles, 24);
+
+    let routines = profiler.routines();
+    let sub_cycles = 2 + 6 + 8 + 6;
+    assert_eq!(routines[&symbol("sub")], RoutineProfile {
+        calls: 3,
+        self_cycles: 3 * sub_cycles,
+        total_cycles: 3 * sub_cycles,
+    });
+    assert_eq!(routines[&symbol("outer")], RoutineProfile {
+        calls: 1,
+        self_cycles: 12,
+        total_cycles: 12 + sub_cycles,
+    });
+    let start = routines[&symbol("start")];
+    assert_eq!(start.total_cycles, profiler.cycles());
+    let self_cycles: u64 = routines.values().map(|routine| routine.self_cycles).sum();
+    assert_eq!(self_cycles, profiler.cycles());
+
+    let calls = profiler.calls();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[&(symbol("start"), symbol("sub"))], 2);
+    assert_eq!(calls[&(symbol("start"), symbol("outer"))], 1);
+    assert_eq!(calls[&(symbol("outer"), symbol("sub"))], 1);
+}
+
+#[test]
+fn interrupts() {
+    // The interrupt sequence's bus accesses differ when the CPU is cycle accurate.
+    for cycle_accurate in [false, true] {
+        let (mut cpu, mut mem, assembly) = setup();
+        let symbol = |name| assembly.symbol(name).unwrap();
+        cpu.cycle_accurate = cycle_accurate;
+        let mut profiler = Profiler::new();
+        run_until(&mut profiler, &mut cpu, &mut mem, symbol("loop"));
+        cpu.reg.interrupt_disable = false;
+        cpu.set_irq(1, true);
+        profiler.step(&mut cpu, &mut mem).unwrap();
+        cpu.set_irq(1, false);
+        assert_eq!(cpu.pc, symbol("irq"));
+        run_until(&mut profiler, &mut cpu, &mut mem, symbol("done"));
+
+        let routines = profiler.routines();
+        // The interrupt sequence and the RTI.
+        assert_eq!(routines[&symbol("irq")], RoutineProfile { calls: 1, self_cycles: 13, total_cycles: 13 });
+        assert_eq!(profiler.calls()[&(symbol("sub"), symbol("irq"))], 1);
+        // The interrupt returned to the right routine.
+        assert_eq!(profiler.calls()[&(symbol("start"), symbol("outer"))], 1);
+        assert_eq!(routines[&symbol("start")].total_cycles, profiler.cycles());
+    }
+}
+
+#[test]
+fn report() {
+    let (mut cpu, mut mem, assembly) = setup();
+    let mut profiler = Profiler::new();
+    run_until(&mut profiler, &mut cpu, &mut mem, assembly.symbol("done").unwrap());
+
+    let label = |addr: u16| match addr {
+        0x0200 => Some("start".to_string()),
+        0x020C => Some("sub".to_string()),
+        0x020F => Some("loop+1".to_string()),
+        _ => None,
+    };
+    let mut report = Vec::new();
+    profiler.write_report(&mut report, label).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], format!("{} cycles profiled", profiler.cycles()));
+    assert_eq!(lines[3], "          24  25.0%          9  $020F loop+1");
+    assert!(lines.contains(&"          66  68.8%           66  68.8%        3  $020C sub"));
+    assert!(lines.contains(&"                                               2  <- $0200 start"));
+    assert!(lines.contains(&"                                               1  -> $020C sub"));
+}
@@ -0,0 +1,68 @@
+use pones_6502::{Cpu6502, CpuVariant, StateError};
+
+mod common;
+use common::Memory;
+
+fn save(cpu: &Cpu6502) -> Vec<u8> {
+    let mut state = Vec::new();
+    cpu.write_state(&mut state).unwrap();
+    state
+}
+
+#[test]
+fn round_trip() {
+    // CLI; then NOPs. Stop with an IRQ and NMI waiting to be serviced.
+    let mut mem = Memory::filled(0xEA);
+    mem.0[0x0200] = 0x58;
+    let mut cpu = Cpu6502::with_variant(CpuVariant::Ricoh2A03);
+    cpu.pc = 0x0200;
+    cpu.reg.interrupt_disable = true;
+    cpu.set_irq(0b101, true);
+    cpu.step(&mut mem).unwrap();
+    cpu.set_nmi(true);
+
+    let state = save(&cpu);
+    let mut loaded = Cpu6502::read_state(&mut state.as_slice()).unwrap();
+    assert_eq!(loaded, cpu);
+
+    // Both service the NMI and then the IRQ at the same points.
+    for _ in 0..4 {
+        assert_eq!(loaded.step(&mut mem), cpu.step(&mut mem));
+        assert_eq!(loaded, cpu);
+    }
+}
+
+#[test]
+fn format_is_stable() {
+    let mut cpu = Cpu6502::new();
+    cpu.reg.a = 0x12;
+    cpu.sp = 0xFD;
+    cpu.pc = 0xC000;
+    cpu.cycles = 7;
+    cpu.set_irq(1, true);
+    assert_eq!(save(&cpu), [
+        b'6', b'5', b'0', b'2', 1,
+        0x12, 0x00, 0x00, 0x20, 0xFD,
+        0x00, 0xC0,
+        7, 0, 0, 0, 0, 0, 0, 0,
+        0, 0xEE, 0,
+        0, 0,
+        1, 0, 0, 0, 0,
+        0, 0,
+    ]);
+}
+
+#[test]
+fn invalid_states() {
+    let state = save(&Cpu6502::new());
+    let read = |state: &[u8]| Cpu6502::read_state(&mut &state[..]);
+
+    assert!(matches!(read(&state[..state.len() - 1]), Err(StateError::Io(_))));
+    assert!(matches!(read(b"NES\x1A"), Err(StateError::BadMagic)));
+    let mut newer = state.clone();
+    newer[4] = 2;
+    assert!(matches!(read(&newer), Err(StateError::UnsupportedVersion(2))));
+    let mut invalid = state.clone();
+    invalid[20] = 2;
+    assert!(matches!(read(&invalid), Err(StateError::Invalid(_))));
+}
@@ -0,0 +1,164 @@
+//! Runs the per-opcode single-step tests from the SingleStepTests (formerly ProcessorTests)
+//! suite. Each `xx.json` file holds a list of cases for opcode `$xx`, giving the registers and
+//! memory before and after one instruction along with every bus access it makes.
+//!
+//! The suite itself isn't checked in. The few cases under `tests/single_step` are written by
+//! hand in its format, so by default this only checks the harness. Point `SINGLE_STEP_TESTS`
+//! at a checkout of the suite to run the real cases: the `6502/v1`, `nes6502/v1` and
+//! `wdc65c02/v1` directories found there are each run on the matching CPU variant.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use pones_6502::{Cpu6502, CpuVariant, DEFAULT_UNSTABLE_MAGIC};
+use serde::Deserialize;
+
+mod common;
+use common::{Access, RecordingBus};
+use Access::*;
+
+const SUITES: [(&str, CpuVariant); 3] = [
+    ("6502/v1", CpuVariant::Nmos6502),
+    ("nes6502/v1", CpuVariant::Ricoh2A03),
+    ("wdc65c02/v1", CpuVariant::Wdc65C02),
+];
+
+// The number of failures to print in full before giving up on the rest.
+const MAX_REPORTED: usize = 20;
+
+#[derive(Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    final_state: State,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+fn root() -> PathBuf {
+    match std::env::var_os("SINGLE_STEP_TESTS") {
+        Some(dir) => dir.into(),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/single_step"),
+    }
+}
+
+// Opcodes that stop the CPU. The suite records them spinning on the bus, which isn't modelled.
+fn is_halting(variant: CpuVariant, opcode: u8) -> bool {
+    match variant {
+        CpuVariant::Wdc65C02 => matches!(opcode, 0xCB | 0xDB),
+        _ => opcode & 0x0F == 0x02 && !matches!(opcode, 0x82 | 0xA2 | 0xC2 | 0xE2),
+    }
+}
+
+// Runs a single case, returning a description of each mismatch.
+fn run_case(variant: CpuVariant, case: &TestCase) -> Vec<String> {
+    let mut bus = RecordingBus::new();
+    for &(addr, value) in &case.initial.ram {
+        bus.mem[addr as usize] = value;
+    }
+    let mut cpu = Cpu6502::with_variant(variant);
+    cpu.cycle_accurate = true;
+    cpu.unstable_magic = DEFAULT_UNSTABLE_MAGIC;
+    cpu.pc = case.initial.pc;
+    cpu.sp = case.initial.s;
+    cpu.reg.a = case.initial.a;
+    cpu.reg.x = case.initial.x;
+    cpu.reg.y = case.initial.y;
+    cpu.reg.set_status(case.initial.p);
+
+    let mut errors = Vec::new();
+    let cycles = match cpu.step(&mut bus) {
+        Ok(cycles) => cycles,
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let expected = &case.final_state;
+    let registers = [
+        ("pc", cpu.pc, expected.pc),
+        ("s", cpu.sp as u16, expected.s as u16),
+        ("a", cpu.reg.a as u16, expected.a as u16),
+        ("x", cpu.reg.x as u16, expected.x as u16),
+        ("y", cpu.reg.y as u16, expected.y as u16),
+        ("p", cpu.reg.get_status(true) as u16, (expected.p | 0x30) as u16),
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            errors.push(format!("{} is ${:02X}, expected ${:02X}", name, actual, expected));
+        }
+    }
+    for &(addr, value) in &expected.ram {
+        let actual = bus.mem[addr as usize];
+        if actual != value {
+            errors.push(format!("${:04X} is ${:02X}, expected ${:02X}", addr, actual, value));
+        }
+    }
+
+    let expected_accesses: Vec<Access> = case
+        .cycles
+        .iter()
+        .map(|(addr, value, kind)| match kind.as_str() {
+            "read" => Read(*addr, *value),
+            "write" => Write(*addr, *value),
+            _ => panic!("unknown bus access {:?}", kind),
+        })
+        .collect();
+    if bus.accesses != expected_accesses {
+        errors.push(format!(
+            "bus accesses are {:?}, expected {:?}",
+            bus.accesses, expected_accesses
+        ));
+    }
+    if cycles as usize != expected_accesses.len() {
+        errors.push(format!("took {} cycles, expected {}", cycles, expected_accesses.len()));
+    }
+    errors
+}
+
+#[test]
+fn single_step_tests() {
+    let root = root();
+    let mut cases = 0;
+    let mut failures = Vec::new();
+    for (dir, variant) in SUITES {
+        let dir = root.join(dir);
+        for opcode in 0..=255 {
+            let path = dir.join(format!("{:02x}.json", opcode));
+            if !path.exists() || is_halting(variant, opcode) {
+                continue;
+            }
+            let json = std::fs::read_to_string(&path).unwrap();
+            let file: Vec<TestCase> = serde_json::from_str(&json)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            for case in &file {
+                cases += 1;
+                let errors = run_case(variant, case);
+                if !errors.is_empty() {
+                    failures.push((variant, case.name.clone(), errors));
+                }
+            }
+        }
+    }
+    assert!(cases > 0, "no single step tests found in {}", root.display());
+
+    if !failures.is_empty() {
+        let mut report = format!("{} of {} cases failed\n", failures.len(), cases);
+        for (variant, name, errors) in failures.iter().take(MAX_REPORTED) {
+            writeln!(report, "{:?} \"{}\":", variant, name).unwrap();
+            for error in errors {
+                writeln!(report, "    {}", error).unwrap();
+            }
+        }
+        panic!("{}", report);
+    }
+}
@@ -0,0 +1,52 @@
+use pones_6502::SymbolTable;
+
+#[test]
+fn nl_files() {
+    let symbols = SymbolTable::parse_nl("$C000#reset#\n$C010/4#table#\n$C020##comment only\n").unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.addr("table"), Some(0xC010));
+    assert_eq!(symbols.label(0xC000), Some("reset"));
+    assert_eq!(symbols.name(0xC000), "reset");
+    assert_eq!(symbols.name(0xC013), "table+3");
+    assert_eq!(symbols.name(0xC110), "$C110");
+    assert_eq!(symbols.nearest_name(0xC110), None);
+    assert_eq!(symbols.name(0x8000), "$8000");
+
+    let error = SymbolTable::parse_nl("$C000#reset#\nC010#table#").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn dbg_files() {
+    let dbg = concat!(
+        "version\tmajor=2,minor=0\n",
+        "info\tcsym=0,file=1,lib=0,line=9,mod=1,scope=1,seg=1,span=4,sym=4,type=3\n",
+        "seg\tid=0,name=\"CODE\",start=0x00C000,size=0x0010,addrsize=absolute,type=ro\n",
+        "sym\tid=0,name=\"LAB_COLD\",addrsize=absolute,scope=0,def=1,ref=3,val=0xC000,seg=0,type=lab\n",
+        "sym\tid=1,name=\"@loop\",addrsize=absolute,scope=0,parent=0,def=2,val=0xC004,seg=0,type=lab\n",
+        "sym\tid=2,name=\"IO_AREA\",addrsize=absolute,scope=0,def=4,val=0xF000,type=equ\n",
+        "sym\tid=3,name=\"V_INPT\",addrsize=absolute,scope=0,def=5,ref=6,val=0x0205,type=lab\n",
+    );
+    let symbols = SymbolTable::parse_dbg(dbg).unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.addr("LAB_COLD"), Some(0xC000));
+    assert_eq!(symbols.addr("V_INPT"), Some(0x0205));
+    assert_eq!(symbols.name(0xC004), "LAB_COLD+4");
+    assert_eq!(symbols.label(0xF000), None);
+
+    let error = SymbolTable::parse_dbg("version\tmajor=2\nsym\tid=0,name=\"x\",val=C000,type=lab").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn lst_files() {
+    let lst = std::fs::read_to_string("tests/klaus/bin/6502_functional_test.lst").unwrap();
+    let symbols = SymbolTable::parse_lst(&lst);
+    assert_eq!(symbols.addr("start"), Some(0x0400));
+    assert_eq!(symbols.addr("range_end"), Some(0x0581));
+    assert_eq!(symbols.label(0x346F), Some("chkdad"));
+    assert_eq!(symbols.name(0x3477), "chkdad+8");
+    // Constants aren't labels.
+    assert_eq!(symbols.addr("zero_page"), None);
+    assert_eq!(symbols.addr("carry"), None);
+}
@@ -1,4 +1,4 @@
-use pones_6502::{Cpu6502, Bus};
+use pones_6502::{Cpu6502, CpuVariant, Bus, SymbolTable};
 
 struct Memory([u8; 65536]);
 
@@ -20,23 +20,43 @@ fn load_mem(name: &str, start_addr: u16) -> Memory {
     Memory(mem)
 }
 
-#[test]
-fn functional_test() {
+// Describes where a test trapped, using the labels from its listing.
+fn trapped_at(listing: &str, addr: u16) -> String {
+    let lst = std::fs::read_to_string(format!("tests/{}", listing)).expect("failed to read listing");
+    match SymbolTable::parse_lst(&lst).nearest_name(addr) {
+        Some(name) => format!("trapped at {:#06X} ({})", addr, name),
+        None => format!("trapped at {:#06X}", addr),
+    }
+}
+
+fn functional_test(mut cpu: Cpu6502) {
     const BIN_START_ADDR: u16 = 0x000A;
     const PROGRAM_START: u16 = 0x0400;
     const SUCCESS_TRAP: u16 = 0x3469;
 
     let mut mem = load_mem("klaus/bin/6502_functional_test.bin", BIN_START_ADDR);
-    let mut cpu = Cpu6502::new();
     cpu.pc = PROGRAM_START;
     loop {
         let prev_pc = cpu.pc;
-        cpu.step(&mut mem);
+        cpu.step(&mut mem).expect("cpu halted");
         if cpu.pc == prev_pc {
             break;
         }
     }
-    assert!(cpu.pc == SUCCESS_TRAP, "trapped at {:#06X}", cpu.pc);
+    if cpu.pc != SUCCESS_TRAP {
+        panic!("{}", trapped_at("klaus/bin/6502_functional_test.lst", cpu.pc));
+    }
+}
+
+#[test]
+fn nmos_functional_test() {
+    functional_test(Cpu6502::new());
+}
+
+// The functional test only uses documented opcodes, so it passes on the 65C02 too.
+#[test]
+fn cmos_functional_test() {
+    functional_test(Cpu6502::with_variant(CpuVariant::Wdc65C02));
 }
 
 #[test]
@@ -53,20 +73,18 @@ fn interrupt_test() {
     cpu.pc = PROGRAM_START;
     mem.write(FEEDBACK_ADDR, 0);
     loop {
-        let prev_feedback = mem.read(FEEDBACK_ADDR);
         let prev_pc = cpu.pc;
-        cpu.step(&mut mem);
+        cpu.step(&mut mem).expect("cpu halted");
         let feedback = mem.read(FEEDBACK_ADDR);
-        if (feedback & !prev_feedback) & NMI_BIT != 0 {
-            cpu.nmi(&mut mem);
-        } else if feedback & IRQ_BIT != 0 {
-            cpu.irq(&mut mem);
-        }
+        cpu.set_irq(IRQ_BIT, feedback & IRQ_BIT != 0);
+        cpu.set_nmi(feedback & NMI_BIT != 0);
         if cpu.pc == prev_pc {
             break;
         }
     }
-    assert!(cpu.pc == SUCCESS_TRAP, "trapped at {:#06X}", cpu.pc);
+    if cpu.pc != SUCCESS_TRAP {
+        panic!("{}", trapped_at("klaus/bin/6502_interrupt_test.lst", cpu.pc));
+    }
 }
 
 #[test]
@@ -89,7 +107,7 @@ fn decimal_test() {
     let mut cpu = Cpu6502::new();
     cpu.pc = PROGRAM_START;
     while cpu.pc != DONE_ADDR {
-        cpu.step(&mut mem);
+        cpu.step(&mut mem).expect("cpu halted");
     }
     if mem.read(ERROR_ADDR) != 0 {
         eprintln!("CB = {}", cpu.reg.y);
@@ -0,0 +1,143 @@
+use pones_6502::{Cpu6502, CpuHalted, CpuVariant};
+
+mod common;
+use common::{Memory, PROGRAM_START};
+
+fn run(program: &[u8], setup: impl FnOnce(&mut Cpu6502)) -> (Cpu6502, Memory) {
+    let (mut cpu, mut mem) = common::setup(CpuVariant::Nmos6502, program);
+    setup(&mut cpu);
+    cpu.step(&mut mem).expect("cpu halted");
+    (cpu, mem)
+}
+
+#[test]
+fn anc() {
+    let (cpu, _) = run(&[0x0B, 0xF0], |cpu| cpu.reg.a = 0x8F);
+    assert_eq!(cpu.reg.a, 0x80);
+    assert!(cpu.reg.carry && cpu.reg.negative);
+}
+
+#[test]
+fn alr() {
+    let (cpu, _) = run(&[0x4B, 0x03], |cpu| cpu.reg.a = 0xFF);
+    assert_eq!(cpu.reg.a, 0x01);
+    assert!(cpu.reg.carry);
+}
+
+#[test]
+fn arr() {
+    let (cpu, _) = run(&[0x6B, 0xFF], |cpu| {
+        cpu.reg.a = 0xC0;
+        cpu.reg.carry = true;
+    });
+    assert_eq!(cpu.reg.a, 0xE0);
+    assert!(cpu.reg.carry && !cpu.reg.overflow && cpu.reg.negative);
+
+    let (cpu, _) = run(&[0x6B, 0xFF], |cpu| {
+        cpu.reg.a = 0xFF;
+        cpu.reg.decimal = true;
+    });
+    assert_eq!(cpu.reg.a, 0xD5);
+    assert!(cpu.reg.carry && !cpu.reg.negative);
+}
+
+#[test]
+fn xaa() {
+    let (cpu, _) = run(&[0x8B, 0xFF], |cpu| {
+        cpu.reg.a = 0x00;
+        cpu.reg.x = 0x0F;
+    });
+    assert_eq!(cpu.reg.a, 0x0E);
+
+    let (cpu, _) = run(&[0x8B, 0xFF], |cpu| {
+        cpu.reg.a = 0x00;
+        cpu.reg.x = 0x0F;
+        cpu.unstable_magic = 0xFF;
+    });
+    assert_eq!(cpu.reg.a, 0x0F);
+}
+
+#[test]
+fn lxa() {
+    let (cpu, _) = run(&[0xAB, 0x3C], |cpu| cpu.unstable_magic = 0x00);
+    assert_eq!((cpu.reg.a, cpu.reg.x), (0x00, 0x00));
+    assert!(cpu.reg.zero);
+
+    let (cpu, _) = run(&[0xAB, 0x3C], |cpu| cpu.unstable_magic = 0xFF);
+    assert_eq!((cpu.reg.a, cpu.reg.x), (0x3C, 0x3C));
+}
+
+#[test]
+fn axs() {
+    let (cpu, _) = run(&[0xCB, 0x02], |cpu| {
+        cpu.reg.a = 0x0F;
+        cpu.reg.x = 0x03;
+    });
+    assert_eq!(cpu.reg.x, 0x01);
+    assert!(cpu.reg.carry);
+
+    let (cpu, _) = run(&[0xCB, 0x04], |cpu| {
+        cpu.reg.a = 0x0F;
+        cpu.reg.x = 0x03;
+    });
+    assert_eq!(cpu.reg.x, 0xFF);
+    assert!(!cpu.reg.carry && cpu.reg.negative);
+}
+
+#[test]
+fn las() {
+    let (cpu, _) = run(&[0xBB, 0x00, 0x02], |cpu| cpu.sp = 0xF0); // LAS $0200,Y
+    assert_eq!((cpu.reg.a, cpu.reg.x, cpu.sp), (0xB0, 0xB0, 0xB0));
+}
+
+#[test]
+fn unstable_stores() {
+    // SHY $1234,X
+    let (_, mem) = run(&[0x9C, 0x34, 0x12], |cpu| {
+        cpu.reg.x = 0x01;
+        cpu.reg.y = 0xFF;
+    });
+    assert_eq!(mem.0[0x1235], 0x13);
+
+    // SHX $12FF,Y, crossing a page and corrupting the high address byte
+    let (_, mem) = run(&[0x9E, 0xFF, 0x12], |cpu| {
+        cpu.reg.x = 0x05;
+        cpu.reg.y = 0x01;
+    });
+    assert_eq!(mem.0[0x0100], 0x01);
+    assert_eq!(mem.0[0x1300], 0x00);
+
+    // AHX $1234,Y
+    let (_, mem) = run(&[0x9F, 0x34, 0x12], |cpu| {
+        cpu.reg.a = 0xF7;
+        cpu.reg.x = 0x3F;
+    });
+    assert_eq!(mem.0[0x1234], 0x13);
+
+    // TAS $1234,Y
+    let (cpu, mem) = run(&[0x9B, 0x34, 0x12], |cpu| {
+        cpu.reg.a = 0xF7;
+        cpu.reg.x = 0x3F;
+    });
+    assert_eq!(cpu.sp, 0x37);
+    assert_eq!(mem.0[0x1234], 0x13);
+}
+
+#[test]
+fn stp() {
+    let mut mem = Memory::filled(0);
+    mem.0[0x0200] = 0x02; // STP
+    mem.0[0xFFFC] = 0x00;
+    mem.0[0xFFFD] = 0x02;
+    let mut cpu = Cpu6502::new();
+    cpu.pc = PROGRAM_START;
+    assert_eq!(cpu.step(&mut mem), Err(CpuHalted { pc: PROGRAM_START }));
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.nmi(&mut mem), 0);
+    assert_eq!(cpu.step(&mut mem), Err(CpuHalted { pc: PROGRAM_START }));
+    assert_eq!(cpu.pc, PROGRAM_START);
+
+    cpu.reset(&mut mem);
+    assert!(!cpu.is_halted());
+    assert_eq!(cpu.pc, PROGRAM_START);
+}
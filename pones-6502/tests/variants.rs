@@ -0,0 +1,99 @@
+use pones_6502::{Cpu6502, CpuVariant};
+
+mod common;
+use common::run;
+
+#[test]
+fn decimal_mode() {
+    // SED; CLC; LDA #$09; ADC #$01
+    let program = [0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01];
+    let (cpu, _) = run(CpuVariant::Nmos6502, &program, 4);
+    assert_eq!(cpu.reg.a, 0x10);
+    let (cpu, _) = run(CpuVariant::Ricoh2A03, &program, 4);
+    assert_eq!(cpu.reg.a, 0x0A);
+    assert!(cpu.reg.decimal);
+
+    #[allow(deprecated)]
+    let cpu = Cpu6502::with_no_decimal();
+    assert_eq!(cpu.variant, CpuVariant::Ricoh2A03);
+}
+
+#[test]
+fn nmos_decimal_flags() {
+    // SED; CLC; LDA #$99; ADC #$01
+    let (cpu, _) = run(CpuVariant::Nmos6502, &[0xF8, 0x18, 0xA9, 0x99, 0x69, 0x01], 4);
+    assert_eq!(cpu.reg.a, 0x00);
+    assert!(cpu.reg.carry);
+    // Z comes from the binary sum, and N and V from the sum before $60 is added.
+    assert!(!cpu.reg.zero);
+    assert!(cpu.reg.negative);
+    assert!(!cpu.reg.overflow);
+
+    // SED; SEC; LDA #$79; ADC #$00
+    let (cpu, _) = run(CpuVariant::Nmos6502, &[0xF8, 0x38, 0xA9, 0x79, 0x69, 0x00], 4);
+    assert_eq!(cpu.reg.a, 0x80);
+    assert!(cpu.reg.overflow);
+    assert!(cpu.reg.negative);
+
+    // SED; SEC; LDA #$00; SBC #$01, with the flags of the binary subtraction.
+    let (cpu, _) = run(CpuVariant::Nmos6502, &[0xF8, 0x38, 0xA9, 0x00, 0xE9, 0x01], 4);
+    assert_eq!(cpu.reg.a, 0x99);
+    assert!(!cpu.reg.carry);
+    assert!(cpu.reg.negative);
+    assert!(!cpu.reg.zero);
+    assert!(!cpu.reg.overflow);
+}
+
+#[test]
+fn address_bus_width() {
+    // LDA #$42; STA $E000; LDA $0000
+    let program = [0xA9, 0x42, 0x8D, 0x00, 0xE0, 0xAD, 0x00, 0x00];
+    let (cpu, mem) = run(CpuVariant::Mos6507, &program, 3);
+    assert_eq!(mem.0[0x0000], 0x42);
+    assert_eq!(mem.0[0xE000], 0x00);
+    assert_eq!(cpu.reg.a, 0x42);
+
+    // The reset vector is read from the mirror at $1FFC.
+    let (mut cpu, mut mem) = run(CpuVariant::Mos6507, &[], 0);
+    mem.0[0x1FFC] = 0x00;
+    mem.0[0x1FFD] = 0xF0;
+    cpu.reset(&mut mem);
+    assert_eq!(cpu.pc, 0xF000);
+}
+
+#[test]
+fn interrupt_lines() {
+    // NOP; NOP
+    let (mut cpu, mut mem) = run(CpuVariant::Mos6507, &[0xEA, 0xEA], 0);
+    cpu.set_nmi(true);
+    cpu.set_irq(1, true);
+    cpu.step(&mut mem).unwrap();
+    assert_eq!(cpu.pc, 0x0201);
+    assert_eq!(cpu.irq_sources(), 0);
+    assert!(!cpu.nmi_line());
+}
+
+#[test]
+fn indirect_jump_bug() {
+    let mut program = vec![0x6C, 0xFF, 0x02];
+    program.resize(0x101, 0);
+    program[0x00FF] = 0x34;
+    program[0x0100] = 0x12;
+    // The NMOS chips fetch the high byte from $0200, which holds the opcode.
+    for variant in [CpuVariant::Nmos6502, CpuVariant::Ricoh2A03, CpuVariant::Mos6507] {
+        let (cpu, _) = run(variant, &program, 1);
+        assert_eq!(cpu.pc, 0x6C34, "{:?}", variant);
+    }
+    let (cpu, _) = run(CpuVariant::Wdc65C02, &program, 1);
+    assert_eq!(cpu.pc, 0x1234);
+}
+
+#[test]
+fn unstable_magic() {
+    // LDA #$00; LXA #$3C
+    let program = [0xA9, 0x00, 0xAB, 0x3C];
+    let (cpu, _) = run(CpuVariant::Ricoh2A03, &program, 2);
+    assert_eq!((cpu.reg.a, cpu.reg.x), (0x3C, 0x3C));
+    let (cpu, _) = run(CpuVariant::Nmos6502, &program, 2);
+    assert_eq!((cpu.reg.a, cpu.reg.x), (0x2C, 0x2C));
+}
@@ -51,6 +51,13 @@ impl Bus for EhBasicBus {
         self.mem[addr as usize]
     }
 
+    fn peek(&self, addr: u16) -> Option<u8> {
+        if addr == IO_READ_ADDR {
+            return None;
+        }
+        Some(self.mem[addr as usize])
+    }
+
     fn write(&mut self, addr: u16, value: u8) {
         if addr == IO_WRITE_ADDR {
             match value {
@@ -62,12 +69,37 @@ impl Bus for EhBasicBus {
     }
 }
 
+// Breakpoints are given as `--break <hex address>`. Hitting one logs the CPU state to stderr.
+fn parse_debugger() -> Debugger {
+    let mut debugger = Debugger::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--break" => {
+                let addr = args.next().expect("expected an address after --break");
+                let addr = addr.trim_start_matches('$');
+                let addr = u16::from_str_radix(addr, 16).expect("invalid breakpoint address");
+                debugger.add_breakpoint(addr);
+            }
+            _ => panic!("unknown argument {:?}", arg),
+        }
+    }
+    debugger
+}
+
 fn main() {
+    let mut debugger = parse_debugger();
     let term = Term::stdout();
     let mut bus = EhBasicBus::new(term);
     let mut cpu = Cpu6502::new();
     cpu.reset(&mut bus);
     loop {
-        cpu.step(&mut bus);
+        match debugger.run(&mut cpu, &mut bus) {
+            StopReason::Halted(halted) => {
+                eprintln!("\r\n{}", halted);
+                std::process::exit(1);
+            }
+            _ => eprintln!("\r\n{}", trace_line(&cpu, &bus, None)),
+        }
     }
 }
@@ -0,0 +1,28 @@
+use pones_6502::{assemble, CpuVariant};
+
+const ROM_START: usize = 0xC000;
+
+// Reassembles the monitor and compares it with the checked in ROM, with the
+// `.include` and the segments of basic.cfg resolved by hand in place of ld65.
+#[test]
+fn assembles_rom() {
+    let basic = std::fs::read_to_string("ehbasic/basic.s")
+        .unwrap()
+        .replace(r#".segment "CODE""#, ".org $C000");
+    // The monitor's code follows on from the end of BASIC's.
+    let source = std::fs::read_to_string("ehbasic/min_mon.s")
+        .unwrap()
+        .replace(r#".segment "CODE""#, "")
+        .replace(r#".include "basic.s""#, &basic)
+        .replace(r#".segment "VECTORS""#, ".org $FFFA");
+    let assembly = assemble(CpuVariant::Nmos6502, &source).unwrap_or_else(|e| panic!("{}", e));
+
+    let mut rom = vec![0; 0x10000 - ROM_START];
+    for chunk in &assembly.chunks {
+        let start = chunk.addr as usize - ROM_START;
+        rom[start..start + chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+    }
+    assert!(rom == std::fs::read("ehbasic/basic.bin").unwrap());
+    let reset_vector = u16::from_le_bytes([rom[0xFFFC - ROM_START], rom[0xFFFD - ROM_START]]);
+    assert_eq!(assembly.symbol("RES_vec"), Some(reset_vector));
+}
@@ -0,0 +1,107 @@
+use std::io::prelude::*;
+use std::io;
+
+use pones_6502::ReadKind;
+use thiserror::Error;
+
+use super::PpuReadKind;
+
+/// Flags for each byte of PRG ROM in a [`CodeDataLog`], laid out like FCEUX's.
+pub mod prg_flags {
+    /// Read by the CPU as part of an instruction.
+    pub const CODE: u8 = 1 << 0;
+    /// Read by the CPU as data.
+    pub const DATA: u8 = 1 << 1;
+    /// Which 8 KB window of `$8000-$FFFF` the byte was mapped into when last read.
+    pub const BANK: u8 = 0b11 << 2;
+    pub const BANK_SHIFT: u8 = 2;
+    /// Read by the CPU as the opcode of an instruction. FCEUX leaves this bit unused,
+    /// and marks opcodes and operands alike as code, so it's only kept in memory and
+    /// isn't saved.
+    pub const OPCODE: u8 = 1 << 7;
+}
+
+/// Flags for each byte of CHR ROM in a [`CodeDataLog`], laid out like FCEUX's.
+pub mod chr_flags {
+    /// Fetched by the PPU while rendering.
+    pub const RENDERED: u8 = 1 << 0;
+    /// Read by the CPU through `PPUDATA`.
+    pub const READ: u8 = 1 << 1;
+}
+
+#[derive(Debug, Error)]
+pub enum CdlError {
+    #[error("io error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("expected {expected} bytes for the cartridge's PRG and CHR ROM, found {found}")]
+    WrongSize { expected: usize, found: usize },
+}
+
+/// A code/data log (CDL), recording how each byte of a cartridge's ROM was used.
+///
+/// The `.cdl` files it saves and loads are FCEUX's: a byte of [`prg_flags`] for each byte
+/// of PRG ROM, followed by a byte of [`chr_flags`] for each byte of CHR ROM. The FCEUX flags
+/// for indirect accesses and PCM audio are kept when loaded, but never set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDataLog {
+    pub prg: Box<[u8]>,
+    pub chr: Box<[u8]>,
+}
+
+impl CodeDataLog {
+    /// An empty log for a cartridge with the given sizes of PRG and CHR ROM.
+    pub fn new(prg_len: usize, chr_len: usize) -> Self {
+        Self {
+            prg: vec![0; prg_len].into_boxed_slice(),
+            chr: vec![0; chr_len].into_boxed_slice(),
+        }
+    }
+
+    /// Load a `.cdl` file, which must have been made for a cartridge with the given
+    /// sizes of PRG and CHR ROM.
+    pub fn load(read: &mut impl Read, prg_len: usize, chr_len: usize) -> Result<Self, CdlError> {
+        let mut data = Vec::new();
+        read.read_to_end(&mut data)?;
+        let expected = prg_len + chr_len;
+        if data.len() != expected {
+            return Err(CdlError::WrongSize { expected, found: data.len() });
+        }
+        let chr = data.split_off(prg_len);
+        Ok(Self {
+            prg: data.into_boxed_slice(),
+            chr: chr.into_boxed_slice(),
+        })
+    }
+
+    /// Save the log as a `.cdl` file, leaving out [`prg_flags::OPCODE`].
+    pub fn save(&self, write: &mut impl Write) -> io::Result<()> {
+        let prg: Vec<u8> = self.prg.iter().map(|&flags| flags & !prg_flags::OPCODE).collect();
+        write.write_all(&prg)?;
+        write.write_all(&self.chr)
+    }
+
+    /// Log a CPU read of the PRG ROM byte at `offset`, which is mapped at `addr`.
+    /// Dummy reads aren't logged, since the CPU doesn't use them.
+    pub fn log_prg_read(&mut self, offset: usize, addr: u16, kind: ReadKind) {
+        use prg_flags::*;
+
+        let flags = match kind {
+            ReadKind::Opcode => OPCODE | CODE,
+            ReadKind::Operand => CODE,
+            ReadKind::Data => DATA,
+            ReadKind::Dummy => return,
+        };
+        let bank = ((addr >> 13) & 0b11) as u8;
+        let byte = &mut self.prg[offset];
+        *byte = (*byte & !BANK) | (bank << BANK_SHIFT) | flags;
+    }
+
+    /// Log a read of the CHR ROM byte at `offset`, either by the PPU while rendering
+    /// or by the CPU through `PPUDATA`.
+    pub fn log_chr_read(&mut self, offset: usize, kind: PpuReadKind) {
+        self.chr[offset] |= match kind {
+            PpuReadKind::Rendering => chr_flags::RENDERED,
+            PpuReadKind::Data => chr_flags::READ,
+        };
+    }
+}
@@ -1,4 +1,8 @@
-use super::NesCart;
+use std::io::prelude::*;
+
+use pones_6502::ReadKind;
+
+use super::{CdlError, CodeDataLog, Mirroring, NesCart, PpuReadKind};
 
 mod mapper;
 mod parse;
@@ -8,7 +12,60 @@ use mapper::INesMapper;
 pub struct INesCart {
     prg_rom: Box<[u8]>,
     chr_rom: Box<[u8]>,
+    /// 8 KB of CHR RAM in place of CHR ROM, if the cartridge has none.
+    chr_ram: Box<[u8]>,
+    /// 4 KB of VRAM for the nametables, with [`Mirroring::FourScreen`].
+    vram: Box<[u8]>,
     mapper: INesMapper,
+    mirroring: Mirroring,
+    cdl: Option<CodeDataLog>,
+}
+
+impl INesCart {
+    /// Start logging how the ROM is used to a new [`CodeDataLog`], replacing any current log.
+    pub fn start_cdl(&mut self) {
+        self.cdl = Some(CodeDataLog::new(self.prg_rom.len(), self.chr_rom.len()));
+    }
+
+    /// Continue logging from a `.cdl` file made for this cartridge.
+    pub fn load_cdl(&mut self, read: &mut impl Read) -> Result<(), CdlError> {
+        self.cdl = Some(CodeDataLog::load(read, self.prg_rom.len(), self.chr_rom.len())?);
+        Ok(())
+    }
+
+    /// The current code/data log, if logging.
+    pub fn cdl(&self) -> Option<&CodeDataLog> {
+        self.cdl.as_ref()
+    }
+
+    /// Stop logging, returning the log.
+    pub fn stop_cdl(&mut self) -> Option<CodeDataLog> {
+        self.cdl.take()
+    }
+
+    // The offset into PRG ROM that the CPU address `addr` is mapped to, if any.
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        use INesMapper::*;
+
+        match self.mapper {
+            NRom => match addr {
+                0x8000..=0xFFFF => Some((addr - 0x8000) as usize % self.prg_rom.len()),
+                _ => None
+            }
+        }
+    }
+
+    // The offset into CHR ROM that the PPU address `addr` is mapped to, if any.
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        use INesMapper::*;
+
+        match self.mapper {
+            NRom => match addr {
+                0x0000..=0x1FFF if (addr as usize) < self.chr_rom.len() => Some(addr as usize),
+                _ => None
+            }
+        }
+    }
 }
 
 impl NesCart for INesCart {
@@ -24,6 +81,27 @@ impl NesCart for INesCart {
         }
     }
 
+    fn cpu_read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        let value = self.cpu_read(addr);
+        if let (Some(offset), Some(cdl)) = (self.prg_rom_offset(addr), &mut self.cdl) {
+            cdl.log_prg_read(offset, addr, kind);
+        }
+        value
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        use INesMapper::*;
+
+        match self.mapper {
+            NRom => match addr {
+                0x8000..=0xFFFF => Some(self.prg_rom[(addr - 0x8000) as usize % self.prg_rom.len()]),
+                _ => Some(0)
+            }
+        }
+    }
+
+    // Matches on the address like the other accessors, which PRG RAM will add arms to.
+    #[allow(clippy::single_match)]
     fn cpu_write(&mut self, addr: u16, value: u8) {
         use INesMapper::*;
         
@@ -35,4 +113,47 @@ impl NesCart for INesCart {
             }
         }
     }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.ppu_peek(addr).unwrap()
+    }
+
+    fn ppu_read_as(&mut self, addr: u16, kind: PpuReadKind) -> u8 {
+        let value = self.ppu_read(addr);
+        if let (Some(offset), Some(cdl)) = (self.chr_rom_offset(addr), &mut self.cdl) {
+            cdl.log_chr_read(offset, kind);
+        }
+        value
+    }
+
+    fn ppu_peek(&self, addr: u16) -> Option<u8> {
+        use INesMapper::*;
+
+        match self.mapper {
+            NRom => Some(match addr {
+                0x0000..=0x1FFF if !self.chr_ram.is_empty() => self.chr_ram[addr as usize],
+                0x0000..=0x1FFF => self.chr_rom.get(addr as usize).copied().unwrap_or(0),
+                _ => self.vram.get((addr & 0x0FFF) as usize).copied().unwrap_or(0),
+            }),
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        use INesMapper::*;
+
+        match self.mapper {
+            NRom => match addr {
+                0x0000..=0x1FFF => if let Some(byte) = self.chr_ram.get_mut(addr as usize) {
+                    *byte = value;
+                },
+                _ => if let Some(byte) = self.vram.get_mut((addr & 0x0FFF) as usize) {
+                    *byte = value;
+                },
+            },
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
 }
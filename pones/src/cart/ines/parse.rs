@@ -3,6 +3,7 @@ use std::io::prelude::*;
 use thiserror::Error;
 
 use super::INesCart;
+use crate::cart::Mirroring;
 use super::mapper::INesMapper;
 
 #[derive(Debug, Error)]
@@ -27,18 +28,29 @@ impl INesCart {
 
         let prg_rom_size = header[4] as usize * 16384;
         let chr_rom_size = header[5] as usize * 8192;
-        let mapper_id = (header[7] << 4) | (header[6] & 0xF);
+        let mapper_id = (header[7] & 0xF0) | (header[6] >> 4);
+        let mirroring = match header[6] & 0b1001 {
+            0b0000 => Mirroring::Horizontal,
+            0b0001 => Mirroring::Vertical,
+            _ => Mirroring::FourScreen,
+        };
         
         let mut prg_rom = vec![0; prg_rom_size].into_boxed_slice();
         let mut chr_rom = vec![0; chr_rom_size].into_boxed_slice();
         read.read_exact(&mut prg_rom)?;
         read.read_exact(&mut chr_rom)?;
         let mapper = INesMapper::from_id(mapper_id)?;
+        let chr_ram_size = if chr_rom_size == 0 { 8192 } else { 0 };
+        let vram_size = if mirroring == Mirroring::FourScreen { 4096 } else { 0 };
 
         Ok(Self {
             prg_rom,
             chr_rom,
+            chr_ram: vec![0; chr_ram_size].into_boxed_slice(),
+            vram: vec![0; vram_size].into_boxed_slice(),
             mapper,
+            mirroring,
+            cdl: None,
         })
     }
 }
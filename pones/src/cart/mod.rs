@@ -1,6 +1,38 @@
+use pones_6502::ReadKind;
+
 mod ines;
+mod cdl;
 
 pub use ines::*;
+pub use cdl::*;
+
+/// How a cartridge wires the console's 2 KB of VRAM to the four nametables at `$2000-$2FFF`.
+/// Mappers that can switch it report the current setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /// `$2000` and `$2400` share the first 1 KB, `$2800` and `$2C00` the second,
+    /// for vertical scrolling.
+    Horizontal,
+    /// `$2000` and `$2800` share the first 1 KB, `$2400` and `$2C00` the second,
+    /// for horizontal scrolling.
+    Vertical,
+    /// All four nametables share the first 1 KB.
+    SingleScreenLower,
+    /// All four nametables share the second 1 KB.
+    SingleScreenUpper,
+    /// The cartridge has its own VRAM for all four nametables, and nametable accesses go
+    /// to [`NesCart::ppu_read`] and [`NesCart::ppu_write`] instead of the console's VRAM.
+    FourScreen,
+}
+
+/// Why the PPU is reading from the cartridge, for [`NesCart::ppu_read_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuReadKind {
+    /// A nametable, attribute or pattern fetch while rendering.
+    Rendering,
+    /// A read by the CPU through `PPUDATA`.
+    Data,
+}
 
 pub trait NesCart {
     /// A read from the part of the CPU address space mapped to the cartridge (`$4020-$FFFF`).
@@ -8,4 +40,49 @@ pub trait NesCart {
 
     /// A write to the part of the CPU address space mapped to the cartridge (`$4020-$FFFF`).
     fn cpu_write(&mut self, addr: u16, value: u8);
+
+    /// Like [`NesCart::cpu_read`], but told what the CPU is going to do with the value.
+    /// See [`pones_6502::Bus::read_as`]. Defaults to [`NesCart::cpu_read`].
+    fn cpu_read_as(&mut self, addr: u16, _kind: ReadKind) -> u8 {
+        self.cpu_read(addr)
+    }
+
+    /// A read from the CPU address space mapped to the cartridge without any side effects,
+    /// or `None` if that isn't possible. See [`pones_6502::Bus::peek`].
+    fn cpu_peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// A read from the pattern tables in the PPU address space (`$0000-$1FFF`), which are
+    /// mapped to the cartridge's CHR ROM or RAM, or from the nametables (`$2000-$3EFF`)
+    /// with [`Mirroring::FourScreen`].
+    fn ppu_read(&mut self, addr: u16) -> u8;
+
+    /// Like [`NesCart::ppu_read`], but told whether the PPU is rendering or the CPU is
+    /// reading through `PPUDATA`. Defaults to [`NesCart::ppu_read`].
+    fn ppu_read_as(&mut self, addr: u16, _kind: PpuReadKind) -> u8 {
+        self.ppu_read(addr)
+    }
+
+    /// A write to the pattern tables in the PPU address space (`$0000-$1FFF`), or to the
+    /// nametables (`$2000-$3EFF`) with [`Mirroring::FourScreen`].
+    fn ppu_write(&mut self, addr: u16, value: u8);
+
+    /// Like [`NesCart::cpu_peek`], for [`NesCart::ppu_read`].
+    fn ppu_peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// Called with the address of every PPU read and write outside palette RAM, including
+    /// those to the console's VRAM, before the access. Mappers like the MMC3 watch the
+    /// address lines this way to count scanlines.
+    fn ppu_addr(&mut self, _addr: u16) {}
+
+    /// How the nametables are currently mirrored.
+    fn mirroring(&self) -> Mirroring;
+
+    /// Whether the cartridge is asserting the CPU's IRQ line.
+    fn irq(&self) -> bool {
+        false
+    }
 }
@@ -1,12 +1,19 @@
-use pones_6502::Cpu6502;
+use pones_6502::{Cpu6502, CpuHalted, CpuVariant, trace_line};
 
 pub mod mem;
 pub mod ppu;
 pub mod cart;
 
-use mem::CpuMemMap;
-use cart::NesCart;
-use ppu::NesPpu;
+use mem::{CpuMemMap, PpuMemMap};
+use cart::{NesCart, PpuReadKind};
+use ppu::{NesPpu, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// The sources sharing the CPU's IRQ line, for [`Cpu6502::set_irq`].
+pub mod irq_source {
+    pub const MAPPER: u8 = 1 << 0;
+    pub const APU_FRAME: u8 = 1 << 1;
+    pub const DMC: u8 = 1 << 2;
+}
 
 pub struct NesEmulator {
     pub cpu_mem: [u8; 2048],
@@ -15,29 +22,108 @@ pub struct NesEmulator {
     pub ppu: NesPpu,
 }
 
+impl Default for NesEmulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NesEmulator {
     pub fn new() -> Self {
+        let mut cpu = Cpu6502::with_variant(CpuVariant::Ricoh2A03);
+        // Mapper and PPU registers can observe the dummy accesses.
+        cpu.cycle_accurate = true;
         Self {
             cpu_mem: [0; 2048],
             ppu_mem: [0; 2048],
-            cpu: Cpu6502::with_no_decimal(),
+            cpu,
             ppu: NesPpu::new(),
         }
     }
 
-    pub fn step(&mut self, cart: &mut impl NesCart) {
-        self.cpu.step(&mut CpuMemMap {
+    /// Execute one CPU instruction, returning the number of CPU cycles it took.
+    /// The PPU runs 3 dots per CPU cycle, in step with the CPU's bus accesses, so the
+    /// CPU sees its registers and NMI line change partway through an instruction.
+    /// Cycles without a bus access, which are only skipped when the CPU isn't
+    /// [cycle accurate](Cpu6502::cycle_accurate), are caught up on afterwards.
+    pub fn step(&mut self, cart: &mut impl NesCart) -> Result<u32, CpuHalted> {
+        self.cpu.set_irq(irq_source::MAPPER, cart.irq());
+        let mut bus = CpuMemMap {
+            cpu_mem: &mut self.cpu_mem,
+            ppu: &mut self.ppu,
+            ppu_mem: &mut self.ppu_mem,
+            cart: &mut *cart,
+            clock_ppu: true,
+            ppu_cycles: 0,
+        };
+        let cycles = self.cpu.step(&mut bus)?;
+        let remaining = cycles - bus.ppu_cycles;
+        let mut bus = PpuMemMap {
+            ppu_mem: &mut self.ppu_mem,
+            cart,
+            kind: PpuReadKind::Rendering,
+        };
+        for _ in 0..remaining * 3 {
+            self.ppu.tick(&mut bus);
+        }
+        Ok(cycles)
+    }
+
+    /// The picture the PPU has drawn, as indexes into the NES's 64 colour master palette.
+    /// Rows still to be drawn this frame show the previous frame.
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.ppu.framebuffer
+    }
+
+    /// The state of the CPU before its next instruction, as a line of a Nintendulator log.
+    pub fn trace_line(&mut self, cart: &mut impl NesCart) -> String {
+        let (scanline, dot) = (self.ppu.scanline, self.ppu.dot);
+        let bus = CpuMemMap {
             cpu_mem: &mut self.cpu_mem,
-            ppu_reg: &mut self.ppu.reg,
+            ppu: &mut self.ppu,
+            ppu_mem: &mut self.ppu_mem,
             cart,
-        });
+            clock_ppu: false,
+            ppu_cycles: 0,
+        };
+        trace_line(&self.cpu, &bus, Some((scanline, dot)))
     }
 
+    /// The CPU's address space, for accessing memory and registers between instructions.
+    /// Accesses don't run the PPU.
     pub fn cpu_mem_map<'m, C: NesCart>(&'m mut self, cart: &'m mut C) -> CpuMemMap<'m, C> {
         CpuMemMap {
             cpu_mem: &mut self.cpu_mem,
-            ppu_reg: &mut self.ppu.reg,
+            ppu: &mut self.ppu,
+            ppu_mem: &mut self.ppu_mem,
+            cart,
+            clock_ppu: false,
+            ppu_cycles: 0,
+        }
+    }
+
+    /// The PPU's address space, apart from the palette RAM in [`NesPpu::palette`].
+    /// Reads from the cartridge are treated like `PPUDATA` reads.
+    pub fn ppu_mem_map<'m, C: NesCart>(&'m mut self, cart: &'m mut C) -> PpuMemMap<'m, C> {
+        PpuMemMap {
+            ppu_mem: &mut self.ppu_mem,
             cart,
+            kind: PpuReadKind::Data,
         }
     }
+
+    /// The CPU together with its memory map, for driving the CPU directly, e.g. with a
+    /// [`Debugger`](pones_6502::Debugger) or [`Profiler`](pones_6502::Profiler).
+    /// The PPU runs along with the CPU's bus accesses, as in [`NesEmulator::step`].
+    pub fn cpu_and_mem_map<'m, C: NesCart>(&'m mut self, cart: &'m mut C) -> (&'m mut Cpu6502, CpuMemMap<'m, C>) {
+        let mem_map = CpuMemMap {
+            cpu_mem: &mut self.cpu_mem,
+            ppu: &mut self.ppu,
+            ppu_mem: &mut self.ppu_mem,
+            cart,
+            clock_ppu: true,
+            ppu_cycles: 0,
+        };
+        (&mut self.cpu, mem_map)
+    }
 }
@@ -1,32 +1,137 @@
-use pones_6502::Bus;
+use pones_6502::{Bus, ReadKind};
 
-use crate::cart::NesCart;
-use crate::ppu::PpuRegisters;
+use crate::cart::{Mirroring, NesCart, PpuReadKind};
+use crate::ppu::NesPpu;
 
 pub struct CpuMemMap<'m, C> {
     pub cpu_mem: &'m mut [u8; 2048],
-    pub ppu_reg: &'m mut PpuRegisters,
+    pub ppu: &'m mut NesPpu,
+    pub ppu_mem: &'m mut [u8; 2048],
     pub cart: &'m mut C,
+    /// Run the PPU for the 3 dots of a CPU cycle before each access, so the CPU sees the
+    /// PPU's registers and NMI line as they are on the cycle of the access.
+    pub clock_ppu: bool,
+    /// The number of CPU cycles the PPU has been run for by `clock_ppu`.
+    pub ppu_cycles: u32,
+}
+
+impl<C: NesCart> CpuMemMap<'_, C> {
+    fn run_ppu(&mut self) {
+        if !self.clock_ppu {
+            return;
+        }
+        let mut bus = PpuMemMap { ppu_mem: self.ppu_mem, cart: self.cart, kind: PpuReadKind::Rendering };
+        for _ in 0..3 {
+            self.ppu.tick(&mut bus);
+        }
+        self.ppu_cycles += 1;
+    }
 }
 
 impl<C: NesCart> Bus for CpuMemMap<'_, C> {
     fn read(&mut self, addr: u16) -> u8 {
+        self.read_as(addr, ReadKind::Data)
+    }
+
+    fn read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        self.run_ppu();
         match addr {
             0x0000..=0x1FFF => self.cpu_mem[addr as usize % self.cpu_mem.len()], // 2 KB internal RAM
-            0x2000..=0x3FFF => *self.ppu_reg.get_mut(addr), // NES PPU registers
+            0x2000..=0x3FFF => self.ppu.read_register(addr, &mut PpuMemMap { ppu_mem: self.ppu_mem, cart: self.cart, kind: PpuReadKind::Data }), // NES PPU registers
             0x4000..=0x4017 => 0, // NES APU and I/O registers
             0x4018..=0x401F => 0, // APU and I/O functionality that is normally disabled
-            0x4020..=0xFFFF => self.cart.cpu_read(addr), // Cartridge space: PRG ROM, PRG RAM, and mapper registers
+            0x4020..=0xFFFF => self.cart.cpu_read_as(addr, kind), // Cartridge space: PRG ROM, PRG RAM, and mapper registers
+        }
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x0000..=0x1FFF => Some(self.cpu_mem[addr as usize % self.cpu_mem.len()]),
+            0x2000..=0x401F => None, // Registers that can't be read without side effects
+            0x4020..=0xFFFF => self.cart.cpu_peek(addr),
         }
     }
 
     fn write(&mut self, addr: u16, value: u8) {
+        self.run_ppu();
         match addr {
             0x0000..=0x1FFF => self.cpu_mem[addr as usize % self.cpu_mem.len()] = value,
-            0x2000..=0x3FFF => *self.ppu_reg.get_mut(addr) = value,
+            0x2000..=0x3FFF => self.ppu.write_register(addr, value, &mut PpuMemMap { ppu_mem: self.ppu_mem, cart: self.cart, kind: PpuReadKind::Data }),
             0x4000..=0x4017 => {},
             0x4018..=0x401F => {},
             0x4020..=0xFFFF => self.cart.cpu_write(addr, value),
         }
     }
+
+    fn nmi_line(&mut self) -> Option<bool> {
+        Some(self.ppu.nmi())
+    }
+}
+
+/// The PPU's address space, as seen on its external bus. The palette RAM at `$3F00-$3FFF`
+/// is inside the PPU, so it's handled by [`NesPpu`], and the nametables underneath show
+/// through here.
+pub struct PpuMemMap<'m, C> {
+    pub ppu_mem: &'m mut [u8; 2048],
+    pub cart: &'m mut C,
+    /// What reads from the cartridge are for, passed on to [`NesCart::ppu_read_as`].
+    pub kind: PpuReadKind,
+}
+
+impl<C: NesCart> PpuMemMap<'_, C> {
+    // The index into VRAM that the nametable address `addr` is mapped to, or `None` if
+    // it's mapped to the cartridge. `$3000-$3FFF` mirrors `$2000-$2FFF`.
+    fn vram_index(&self, addr: u16) -> Option<usize> {
+        use Mirroring::*;
+
+        let nametable = (addr >> 10) & 0b11;
+        let page = match self.cart.mirroring() {
+            Horizontal => nametable >> 1,
+            Vertical => nametable & 1,
+            SingleScreenLower => 0,
+            SingleScreenUpper => 1,
+            FourScreen => return None,
+        };
+        Some((page << 10 | addr & 0x3FF) as usize)
+    }
+}
+
+impl<C: NesCart> Bus for PpuMemMap<'_, C> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        self.cart.ppu_addr(addr);
+        match addr {
+            0x0000..=0x1FFF => self.cart.ppu_read_as(addr, self.kind), // Pattern tables
+            0x2000..=0x3FFF => match self.vram_index(addr) { // Nametables
+                Some(index) => self.ppu_mem[index],
+                None => self.cart.ppu_read_as(0x2000 | addr & 0x0FFF, self.kind),
+            },
+            0x4000.. => unreachable!(),
+        }
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => self.cart.ppu_peek(addr),
+            0x2000..=0x3FFF => match self.vram_index(addr) {
+                Some(index) => Some(self.ppu_mem[index]),
+                None => self.cart.ppu_peek(0x2000 | addr & 0x0FFF),
+            },
+            0x4000.. => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr & 0x3FFF;
+        self.cart.ppu_addr(addr);
+        match addr {
+            0x0000..=0x1FFF => self.cart.ppu_write(addr, value),
+            0x2000..=0x3FFF => match self.vram_index(addr) {
+                Some(index) => self.ppu_mem[index] = value,
+                None => self.cart.ppu_write(0x2000 | addr & 0x0FFF, value),
+            },
+            0x4000.. => unreachable!(),
+        }
+    }
 }
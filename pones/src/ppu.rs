@@ -1,39 +1,568 @@
-#[derive(Debug, Default)]
+use pones_6502::Bus;
+
+#[derive(Debug)]
 pub struct NesPpu {
     pub reg: PpuRegisters,
+    /// Object attribute memory: 64 sprites of 4 bytes each, the Y coordinate, tile index,
+    /// [`attribute_bits`] and X coordinate.
+    pub oam: [u8; 256],
+    /// Secondary OAM: the sprites found on the current scanline for the next, up to 8.
+    pub secondary_oam: [u8; 32],
+    /// Palette RAM at `$3F00-$3FFF`: the background palettes, then the sprite palettes.
+    /// Use [`palette_index`] to find the entry for an address.
+    pub palette: [u8; 32],
+    /// The scanline being drawn. 0-239 are visible, 241-260 are vertical blanking, and 261
+    /// is the pre-render scanline, which prepares the first tiles of the next frame.
+    pub scanline: u16,
+    /// The dot within the scanline, from 0 to 340.
+    pub dot: u16,
+    /// The number of frames completed.
+    pub frame: u64,
+    /// The picture, a row at a time, as indexes into the NES's 64 colour master palette.
+    pub framebuffer: Box<[u8; SCREEN_WIDTH * SCREEN_HEIGHT]>,
+    background: Background,
+    sprites: Sprites,
+}
+
+impl Default for NesPpu {
+    fn default() -> Self {
+        Self {
+            reg: PpuRegisters::default(),
+            oam: [0; 256],
+            secondary_oam: [0xFF; 32],
+            palette: [0; 32],
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            framebuffer: Box::new([0; SCREEN_WIDTH * SCREEN_HEIGHT]),
+            background: Background::default(),
+            sprites: Sprites::default(),
+        }
+    }
+}
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+/// Bits of [`PpuRegisters::ppu_ctrl`].
+pub mod ctrl_bits {
+    pub const NAMETABLE: u8 = 0b11;
+    pub const INCREMENT_32: u8 = 1 << 2;
+    pub const SPRITE_PATTERN_TABLE: u8 = 1 << 3;
+    pub const BACKGROUND_PATTERN_TABLE: u8 = 1 << 4;
+    pub const SPRITE_8X16: u8 = 1 << 5;
+    pub const NMI_ENABLE: u8 = 1 << 7;
+}
+
+/// Bits of [`PpuRegisters::ppu_mask`].
+pub mod mask_bits {
+    pub const GREYSCALE: u8 = 1 << 0;
+    pub const BACKGROUND_LEFT_COLUMN: u8 = 1 << 1;
+    pub const SPRITES_LEFT_COLUMN: u8 = 1 << 2;
+    pub const BACKGROUND: u8 = 1 << 3;
+    pub const SPRITES: u8 = 1 << 4;
+    pub const EMPHASIS: u8 = 0b111 << 5;
+}
+
+/// Bits of the attribute byte of a sprite in [`NesPpu::oam`].
+pub mod attribute_bits {
+    pub const PALETTE: u8 = 0b11;
+    pub const BEHIND_BACKGROUND: u8 = 1 << 5;
+    pub const FLIP_HORIZONTAL: u8 = 1 << 6;
+    pub const FLIP_VERTICAL: u8 = 1 << 7;
+}
+
+/// Bits of [`PpuRegisters::ppu_status`].
+pub mod status_bits {
+    pub const SPRITE_OVERFLOW: u8 = 1 << 5;
+    pub const SPRITE_ZERO_HIT: u8 = 1 << 6;
+    pub const VBLANK: u8 = 1 << 7;
+}
+
+// The bits of the attribute byte of a sprite that exist in OAM.
+const OAM_ATTRIBUTE_MASK: u8 = 0b1110_0011;
+const PALETTE_START: u16 = 0x3F00;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const SCANLINES_PER_FRAME: u16 = 262;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
+/// The index into [`NesPpu::palette`] of the palette RAM address `addr`. Palette RAM is
+/// mirrored every 32 bytes, and the first entry of each sprite palette (`$3F10`, `$3F14`,
+/// `$3F18` and `$3F1C`) mirrors the same entry of the background palettes.
+pub fn palette_index(addr: u16) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 { index & 0x0F } else { index }
 }
 
 impl NesPpu {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Whether the PPU is asserting the CPU's NMI line, which it does during vertical
+    /// blanking if NMIs are enabled in `PPUCTRL`.
+    pub fn nmi(&self) -> bool {
+        self.reg.ppu_status & status_bits::VBLANK != 0 && self.reg.ppu_ctrl & ctrl_bits::NMI_ENABLE != 0
+    }
+
+    /// Run the PPU for one dot, fetching from its address space `bus`. Each visible dot
+    /// draws a pixel to the [`framebuffer`](Self::framebuffer).
+    pub fn tick(&mut self, bus: &mut impl Bus) {
+        use status_bits::*;
+
+        let rendering = self.reg.ppu_mask & (mask_bits::BACKGROUND | mask_bits::SPRITES) != 0;
+        let visible = self.scanline < SCREEN_HEIGHT as u16;
+        let rendering_scanline = rendering && (visible || self.scanline == PRE_RENDER_SCANLINE);
+        if rendering_scanline {
+            self.fetch_background(bus);
+        }
+        if visible && (1..=256).contains(&self.dot) {
+            self.draw_pixel();
+        }
+        if rendering_scanline {
+            self.fetch_sprites(bus);
+        }
+        if self.dot == 1 {
+            match self.scanline {
+                VBLANK_SCANLINE => self.reg.ppu_status |= VBLANK,
+                PRE_RENDER_SCANLINE => self.reg.ppu_status &= !(VBLANK | SPRITE_ZERO_HIT | SPRITE_OVERFLOW),
+                _ => {}
+            }
+        }
+
+        // The last dot of the pre-render scanline is skipped on every other frame
+        // while rendering.
+        let skip_dot = rendering && self.scanline == PRE_RENDER_SCANLINE && self.frame % 2 == 1;
+        self.dot += 1;
+        if self.dot == DOTS_PER_SCANLINE - skip_dot as u16 {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == SCANLINES_PER_FRAME {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+    }
+
+    // The background fetches for the current dot of a visible or pre-render scanline,
+    // which fill the shift registers a tile ahead, and the updates to `v` that go with them.
+    fn fetch_background(&mut self, bus: &mut impl Bus) {
+        let bg = &mut self.background;
+        let reg = &mut self.reg;
+        let dot = self.dot;
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            bg.shift();
+        }
+        if (1..=256).contains(&dot) || (321..=336).contains(&dot) {
+            let v = reg.v;
+            let pattern_table = if reg.ppu_ctrl & ctrl_bits::BACKGROUND_PATTERN_TABLE != 0 { 0x1000 } else { 0 };
+            let pattern_addr = pattern_table | (bg.tile as u16) << 4 | v >> 12;
+            match dot % 8 {
+                1 => {
+                    bg.reload();
+                    bg.tile = bus.read(0x2000 | (v & 0x0FFF));
+                }
+                3 => {
+                    let attribute = bus.read(0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07));
+                    // Each byte covers 4 by 4 tiles, 2 bits for each 2 by 2 quadrant.
+                    let shift = ((v >> 4) & 0b100) | (v & 0b10);
+                    bg.attribute = (attribute >> shift) & 0b11;
+                }
+                5 => bg.pattern_low = bus.read(pattern_addr),
+                7 => bg.pattern_high = bus.read(pattern_addr | 8),
+                0 => reg.increment_coarse_x(),
+                _ => {}
+            }
+        }
+        match dot {
+            256 => reg.increment_y(),
+            257 => reg.v = (reg.v & !0x041F) | (reg.t & 0x041F),
+            280..=304 if self.scanline == PRE_RENDER_SCANLINE => {
+                reg.v = (reg.v & !0x7BE0) | (reg.t & 0x7BE0);
+            }
+            // Unused nametable fetches, which some mappers watch for.
+            337 | 339 => {
+                bus.read(0x2000 | (reg.v & 0x0FFF));
+            }
+            _ => {}
+        }
+    }
+
+    // Sprite evaluation and the sprite fetches for the current dot of a visible or pre-render
+    // scanline, which find the sprites on this scanline and load them to be drawn on the next.
+    // Evaluation is done all at once, at the end of the visible part of the scanline.
+    fn fetch_sprites(&mut self, bus: &mut impl Bus) {
+        match self.dot {
+            64 => self.secondary_oam = [0xFF; 32],
+            256 => self.evaluate_sprites(),
+            257..=320 => {
+                self.reg.oam_addr = 0;
+                let slot = (self.dot - 257) as usize / 8;
+                let [y, tile, attribute, x] = self.secondary_oam[slot * 4..][..4] else { unreachable!() };
+                // The sprite pattern fetches take the place of the background's,
+                // including the nametable and attribute fetches, which are unused.
+                match (self.dot - 257) % 8 {
+                    0 | 2 => {
+                        bus.read(0x2000 | (self.reg.v & 0x0FFF));
+                    }
+                    phase @ (4 | 6) => {
+                        let plane = usize::from(phase == 6);
+                        let pattern = bus.read(self.sprite_pattern_addr(y, tile, attribute) | (plane as u16) << 3);
+                        let sprite = &mut self.sprites.slots[slot];
+                        // Empty slots fetch tile $FF, but draw nothing.
+                        sprite.pattern[plane] = if slot >= self.sprites.found {
+                            0
+                        } else if attribute & attribute_bits::FLIP_HORIZONTAL != 0 {
+                            pattern.reverse_bits()
+                        } else {
+                            pattern
+                        };
+                        sprite.attribute = attribute;
+                        sprite.x = x;
+                    }
+                    _ => {}
+                }
+                if self.dot == 320 {
+                    self.sprites.count = self.sprites.found;
+                    self.sprites.zero = self.sprites.zero_found;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Copy the sprites on the current scanline from OAM to secondary OAM, setting the
+    // overflow flag if there are more than 8, buggily.
+    fn evaluate_sprites(&mut self) {
+        let height = if self.reg.ppu_ctrl & ctrl_bits::SPRITE_8X16 != 0 { 16 } else { 8 };
+        let in_range = |y: u8| (0..height).contains(&(self.scanline as i32 - y as i32));
+        let sprites = &mut self.sprites;
+        sprites.found = 0;
+        sprites.zero_found = false;
+        if self.scanline == PRE_RENDER_SCANLINE {
+            // Nothing is drawn from here on the first scanline.
+            return;
+        }
+
+        let mut n = 0;
+        while n < 64 && sprites.found < 8 {
+            let sprite = &self.oam[n * 4..][..4];
+            if in_range(sprite[0]) {
+                self.secondary_oam[sprites.found * 4..][..4].copy_from_slice(sprite);
+                sprites.found += 1;
+                sprites.zero_found |= n == 0;
+            }
+            n += 1;
+        }
+        // Once secondary OAM is full, the PPU looks for a 9th sprite, but increments the
+        // offset of the byte it reads along with the sprite index, so it checks tile
+        // indices, attributes and X coordinates as if they were Y coordinates.
+        let mut m = 0;
+        while n < 64 {
+            if in_range(self.oam[n * 4 + m]) {
+                self.reg.ppu_status |= status_bits::SPRITE_OVERFLOW;
+                break;
+            }
+            n += 1;
+            m = (m + 1) % 4;
+        }
+    }
+
+    // The address of the low plane of the row of the sprite in secondary OAM to draw on
+    // the next scanline.
+    fn sprite_pattern_addr(&self, y: u8, tile: u8, attribute: u8) -> u16 {
+        let ctrl = self.reg.ppu_ctrl;
+        let tall = ctrl & ctrl_bits::SPRITE_8X16 != 0;
+        let height = if tall { 16 } else { 8 };
+        let mut row = (self.scanline as u8).wrapping_sub(y) & (height - 1);
+        if attribute & attribute_bits::FLIP_VERTICAL != 0 {
+            row = height - 1 - row;
+        }
+        let (pattern_table, tile) = if tall {
+            // The bottom bit of the tile index selects the pattern table instead,
+            // and the bottom half is the next tile.
+            ((tile as u16 & 1) << 12, (tile & 0xFE) + row / 8)
+        } else if ctrl & ctrl_bits::SPRITE_PATTERN_TABLE != 0 {
+            (0x1000, tile)
+        } else {
+            (0, tile)
+        };
+        pattern_table | (tile as u16) << 4 | (row % 8) as u16
+    }
+
+    fn draw_pixel(&mut self) {
+        use mask_bits::*;
+
+        let x = self.dot as usize - 1;
+        let mask = self.reg.ppu_mask;
+        let background = if mask & BACKGROUND != 0 && (x >= 8 || mask & BACKGROUND_LEFT_COLUMN != 0) {
+            self.background.pixel(self.reg.x)
+        } else {
+            0
+        };
+        let sprite = if mask & SPRITES != 0 && (x >= 8 || mask & SPRITES_LEFT_COLUMN != 0) {
+            self.sprites.pixel(x)
+        } else {
+            None
+        };
+        let pixel = match sprite {
+            Some(sprite) if background != 0 => {
+                // Sprite 0 hit can't happen at the last pixel of the scanline.
+                if sprite.zero && x != 255 {
+                    self.reg.ppu_status |= status_bits::SPRITE_ZERO_HIT;
+                }
+                if sprite.behind_background { background } else { sprite.pixel }
+            }
+            Some(sprite) => sprite.pixel,
+            None => background,
+        };
+        // Transparent pixels show the backdrop colour at $3F00.
+        let colour = self.palette[palette_index(PALETTE_START | pixel as u16)];
+        let colour = if mask & GREYSCALE != 0 { colour & 0x30 } else { colour & 0x3F };
+        self.framebuffer[self.scanline as usize * SCREEN_WIDTH + x] = colour;
+    }
+
+    /// A CPU read of the register at `addr` in `$2000-$3FFF`, which mirrors the eight
+    /// registers. `bus` is the PPU's address space, which `PPUDATA` reads from, apart from
+    /// the palette RAM.
+    ///
+    /// Write-only registers and the unused bits of `PPUSTATUS` return the open bus value,
+    /// the last value written to or read from any register. It never decays.
+    pub fn read_register(&mut self, addr: u16, bus: &mut impl Bus) -> u8 {
+        let reg = &mut self.reg;
+        let value = match addr % 8 {
+            2 => {
+                let value = (reg.ppu_status & 0xE0) | (reg.io_latch & 0x1F);
+                reg.ppu_status &= !status_bits::VBLANK;
+                reg.w = false;
+                value
+            }
+            4 => {
+                let value = self.oam[reg.oam_addr as usize];
+                if reg.oam_addr % 4 == 2 {
+                    value & OAM_ATTRIBUTE_MASK
+                } else {
+                    value
+                }
+            }
+            7 => {
+                let addr = reg.v & 0x3FFF;
+                let value = if addr >= PALETTE_START {
+                    // Palette reads skip the buffer, which is filled from the
+                    // nametable mirrored underneath instead. Palette entries are 6 bits.
+                    reg.read_buffer = bus.read(addr - 0x1000);
+                    (self.palette[palette_index(addr)] & 0x3F) | (reg.io_latch & 0xC0)
+                } else {
+                    std::mem::replace(&mut reg.read_buffer, bus.read(addr))
+                };
+                reg.increment_v();
+                value
+            }
+            _ => reg.io_latch,
+        };
+        reg.io_latch = value;
+        value
+    }
+
+    /// A CPU write to the register at `addr` in `$2000-$3FFF`, which mirrors the eight
+    /// registers. `bus` is the PPU's address space, which `PPUDATA` writes to, apart from
+    /// the palette RAM.
+    pub fn write_register(&mut self, addr: u16, value: u8, bus: &mut impl Bus) {
+        let reg = &mut self.reg;
+        reg.io_latch = value;
+        match addr % 8 {
+            0 => {
+                reg.ppu_ctrl = value;
+                reg.t = (reg.t & !0x0C00) | ((value & ctrl_bits::NAMETABLE) as u16) << 10;
+            }
+            1 => reg.ppu_mask = value,
+            2 => {}
+            3 => reg.oam_addr = value,
+            4 => {
+                self.oam[reg.oam_addr as usize] = value;
+                reg.oam_addr = reg.oam_addr.wrapping_add(1);
+            }
+            5 => {
+                let value = value as u16;
+                if !reg.w {
+                    reg.t = (reg.t & !0x001F) | value >> 3;
+                    reg.x = (value & 0b111) as u8;
+                } else {
+                    reg.t = (reg.t & !0x73E0) | (value & 0b111) << 12 | (value & 0xF8) << 2;
+                }
+                reg.w = !reg.w;
+            }
+            6 => {
+                let value = value as u16;
+                if !reg.w {
+                    // The top bit of the 15 bit register is cleared too.
+                    reg.t = (reg.t & 0x00FF) | (value & 0x3F) << 8;
+                } else {
+                    reg.t = (reg.t & 0xFF00) | value;
+                    reg.v = reg.t;
+                }
+                reg.w = !reg.w;
+            }
+            7 => {
+                let addr = reg.v & 0x3FFF;
+                if addr >= PALETTE_START {
+                    self.palette[palette_index(addr)] = value;
+                } else {
+                    bus.write(addr, value);
+                }
+                reg.increment_v();
+            }
+            8.. => unreachable!(),
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+/// The PPU's registers, as seen by the CPU at `$2000-$2007`, along with the internal
+/// registers they control. `v`, `t`, `x` and `w` are the "loopy" registers.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct PpuRegisters {
     pub ppu_ctrl: u8,   // [VPHB SINN] NMI enable (V), PPU master/slave (P), sprite height (H), background tile select (B), sprite tile select (S), increment mode (I), nametable select (NN)
     pub ppu_mask: u8,   // [BGRs bMmG] color emphasis (BGR), sprite enable (s), background enable (b), sprite left column enable (M), background left column enable (m), greyscale (G)
     pub ppu_status: u8, // [VSO- ----] vblank (V), sprite 0 hit (S), sprite overflow (O); read resets write pair for $2005/$2006
     pub oam_addr: u8,   // [aaaa aaaa] OAM read/write address
-    pub oam_data: u8,   // [dddd dddd] OAM data read/write
-    pub ppu_scroll: u8, // [xxxx xxxx] fine scroll position (two writes: X scroll, Y scroll)
-    pub ppu_addr: u8,   // [aaaa aaaa] PPU read/write address (two writes: most significant byte, least significant byte)
-    pub ppu_data: u8,   // [dddd dddd] PPU data read/write
-    // pub oam_dma: u8,    // [aaaa aaaa] OAM DMA high address
+    pub v: u16,         // [.yyy NNYY YYYX XXXX] current VRAM address: fine Y scroll (y), nametable (N), coarse Y (Y), coarse X (X)
+    pub t: u16,         // [.yyy NNYY YYYX XXXX] temporary VRAM address, the scroll position at the top left of the screen
+    pub x: u8,          // [.... .xxx] fine X scroll
+    pub w: bool,        // whether the next write to $2005/$2006 is the second of the pair
+    pub read_buffer: u8, // [dddd dddd] the value the next $2007 read returns
+    pub io_latch: u8,   // [dddd dddd] the last value written to or read from a register, read back as open bus
 }
 
 impl PpuRegisters {
-    pub fn get_mut(&mut self, addr: u16) -> &mut u8 {
-        match addr % 8 {
-            0 => &mut self.ppu_ctrl,
-            1 => &mut self.ppu_mask,
-            2 => &mut self.ppu_status,
-            3 => &mut self.oam_addr,
-            4 => &mut self.oam_data,
-            5 => &mut self.ppu_scroll,
-            6 => &mut self.ppu_addr,
-            7 => &mut self.ppu_data,
-            8.. => unreachable!(),
+    fn increment_v(&mut self) {
+        let increment = if self.ppu_ctrl & ctrl_bits::INCREMENT_32 != 0 { 32 } else { 1 };
+        self.v = self.v.wrapping_add(increment) & 0x7FFF;
+    }
+
+    // Move `v` to the next tile to the right, wrapping into the next nametable across.
+    fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v = (self.v & !0x001F) ^ 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Move `v` down a row of pixels, wrapping into the next nametable down after the 30th
+    // row of tiles. Coarse Y values of 30 and 31, in the attribute table, wrap to 0 without
+    // switching nametables.
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
         }
+        self.v &= !0x7000;
+        let coarse_y = match (self.v & 0x03E0) >> 5 {
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.v = (self.v & !0x03E0) | coarse_y << 5;
+    }
+}
+
+/// The background half of the rendering pipeline: the latches filled by the fetches for
+/// the next tile, and the shift registers which hold two tiles and shift a pixel out per dot.
+/// The next tile is loaded into the low byte of each shift register.
+#[derive(Debug, Default, Clone)]
+struct Background {
+    tile: u8,
+    attribute: u8,
+    pattern_low: u8,
+    pattern_high: u8,
+    pattern_shift: [u16; 2],
+    attribute_shift: [u16; 2],
+}
+
+impl Background {
+    fn shift(&mut self) {
+        for register in self.pattern_shift.iter_mut().chain(&mut self.attribute_shift) {
+            *register <<= 1;
+        }
+    }
+
+    fn reload(&mut self) {
+        let attribute_bits = |bit: u8| if self.attribute & bit != 0 { 0xFF } else { 0x00 };
+        let loads = [
+            self.pattern_low as u16,
+            self.pattern_high as u16,
+            attribute_bits(0b01),
+            attribute_bits(0b10),
+        ];
+        let registers = self.pattern_shift.iter_mut().chain(&mut self.attribute_shift);
+        for (register, load) in registers.zip(loads) {
+            *register = (*register & 0xFF00) | load;
+        }
+    }
+
+    // The pixel under the fine X scroll `x`, as an index into the background palettes,
+    // or 0 if transparent.
+    fn pixel(&self, x: u8) -> u8 {
+        let bit = |register: u16| ((register << x) >> 15) as u8;
+        let pixel = bit(self.pattern_shift[1]) << 1 | bit(self.pattern_shift[0]);
+        if pixel == 0 {
+            return 0;
+        }
+        (bit(self.attribute_shift[1]) << 1 | bit(self.attribute_shift[0])) << 2 | pixel
+    }
+}
+
+/// The sprites to draw on the current scanline, loaded from secondary OAM during the
+/// previous one, and the results of the evaluation for the next.
+#[derive(Debug, Default, Clone)]
+struct Sprites {
+    slots: [SpriteSlot; 8],
+    count: usize,
+    /// Whether the sprite in the first slot is sprite 0.
+    zero: bool,
+    found: usize,
+    zero_found: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SpriteSlot {
+    /// The row of the sprite on the scanline, low plane then high plane, already flipped
+    /// horizontally if need be.
+    pattern: [u8; 2],
+    attribute: u8,
+    x: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpritePixel {
+    /// An index into the sprite palettes, which follow the background palettes.
+    pixel: u8,
+    behind_background: bool,
+    /// Whether the pixel is from sprite 0, for sprite 0 hit.
+    zero: bool,
+}
+
+impl Sprites {
+    // The first opaque pixel of the sprites at `x`. Its priority decides whether it's in front
+    // of the background, even if a lower priority sprite is in front.
+    fn pixel(&self, x: usize) -> Option<SpritePixel> {
+        self.slots[..self.count].iter().enumerate().find_map(|(i, sprite)| {
+            let column = x.wrapping_sub(sprite.x as usize);
+            if column >= 8 {
+                return None;
+            }
+            let bit = |plane: u8| (plane << column) >> 7;
+            let pixel = bit(sprite.pattern[1]) << 1 | bit(sprite.pattern[0]);
+            (pixel != 0).then_some(SpritePixel {
+                pixel: 0x10 | (sprite.attribute & attribute_bits::PALETTE) << 2 | pixel,
+                behind_background: sprite.attribute & attribute_bits::BEHIND_BACKGROUND != 0,
+                zero: i == 0 && self.zero,
+            })
+        })
     }
 }
@@ -0,0 +1,128 @@
+use pones::NesEmulator;
+use pones::cart::{INesCart, Mirroring, NesCart};
+use pones_6502::Bus;
+
+// An NROM cartridge with 16 KB of PRG ROM, all zeros, and `chr_banks` 8 KB banks of CHR ROM.
+fn ines(flags: u8, chr_banks: u8) -> INesCart {
+    let mut rom = b"NES\x1A\x01\0\0\0\0\0\0\0\0\0\0\0".to_vec();
+    rom[5] = chr_banks;
+    rom[6] = flags;
+    rom.resize(16 + 16384 + chr_banks as usize * 8192, 0);
+    INesCart::parse(&mut rom.as_slice()).unwrap()
+}
+
+// A cartridge with 32 KB of PRG RAM, which records the PPU's addresses and can assert IRQ.
+struct TestCart {
+    prg: Vec<u8>,
+    ppu_addrs: Vec<u16>,
+    irq: bool,
+}
+
+impl NesCart for TestCart {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.prg[addr as usize % self.prg.len()]
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        let len = self.prg.len();
+        self.prg[addr as usize % len] = value;
+    }
+
+    fn ppu_read(&mut self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _value: u8) {}
+
+    fn ppu_addr(&mut self, addr: u16) {
+        self.ppu_addrs.push(addr);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::SingleScreenUpper
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+fn test_cart() -> TestCart {
+    TestCart { prg: vec![0xEA; 32768], ppu_addrs: Vec::new(), irq: false }
+}
+
+#[test]
+fn chr_ram() {
+    let mut nes = NesEmulator::new();
+    let mut cart = ines(0, 0);
+    let mut bus = nes.ppu_mem_map(&mut cart);
+    bus.write(0x1FFF, 0x5A);
+    assert_eq!(bus.read(0x1FFF), 0x5A);
+
+    // But CHR ROM can't be written.
+    let mut cart = ines(0, 1);
+    let mut bus = nes.ppu_mem_map(&mut cart);
+    bus.write(0x1FFF, 0x5A);
+    assert_eq!(bus.read(0x1FFF), 0x00);
+}
+
+#[test]
+fn four_screen() {
+    let mut nes = NesEmulator::new();
+    let mut cart = ines(0b1000, 1);
+    assert_eq!(cart.mirroring(), Mirroring::FourScreen);
+    let mut bus = nes.ppu_mem_map(&mut cart);
+    for (i, addr) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+        bus.write(addr, i as u8 + 1);
+    }
+    assert_eq!(bus.read(0x2C00), 4);
+    assert_eq!(bus.read(0x3400), 2);
+    assert_eq!(cart.ppu_peek(0x2800), Some(3));
+    assert!(nes.ppu_mem.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn single_screen() {
+    let mut nes = NesEmulator::new();
+    let mut cart = test_cart();
+    let mut bus = nes.ppu_mem_map(&mut cart);
+    bus.write(0x2C10, 0x77);
+    assert_eq!(bus.read(0x2010), 0x77);
+    assert_eq!(nes.ppu_mem[0x410], 0x77);
+}
+
+#[test]
+fn ppu_addr() {
+    let mut nes = NesEmulator::new();
+    let mut cart = test_cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    for addr in [0x1FFE_u16, 0x3F00] {
+        let [low, high] = addr.to_le_bytes();
+        bus.write(0x2006, high);
+        bus.write(0x2006, low);
+        bus.write(0x2007, 0);
+    }
+    // The palette write stays inside the PPU.
+    assert_eq!(cart.ppu_addrs, [0x1FFE]);
+
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(0x2006, 0x23);
+    bus.write(0x2006, 0xC0);
+    bus.read(0x2007);
+    assert_eq!(cart.ppu_addrs, [0x1FFE, 0x23C0]);
+}
+
+#[test]
+fn irq() {
+    let mut nes = NesEmulator::new();
+    let mut cart = test_cart();
+    cart.prg[0x7FFE..].copy_from_slice(&[0x00, 0x90]);
+    nes.cpu.pc = 0x8000;
+    nes.cpu.reg.interrupt_disable = false;
+    nes.step(&mut cart).unwrap();
+    assert_eq!(nes.cpu.pc, 0x8001);
+
+    cart.irq = true;
+    nes.step(&mut cart).unwrap();
+    assert_eq!(nes.cpu.pc, 0x9000);
+}
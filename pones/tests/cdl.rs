@@ -0,0 +1,103 @@
+use pones::NesEmulator;
+use pones::cart::{chr_flags, prg_flags, CdlError, INesCart};
+use pones::ppu::mask_bits;
+use pones_6502::Bus;
+
+// An NROM cartridge with 16 KB of PRG ROM, mirrored at $8000 and $C000, and 8 KB of CHR ROM.
+fn cart(program: &[u8]) -> INesCart {
+    let mut rom = b"NES\x1A\x01\x01\0\0\0\0\0\0\0\0\0\0".to_vec();
+    let mut prg_rom = vec![0; 16384];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x0100] = 0x42;
+    rom.extend(prg_rom);
+    rom.extend([0; 8192]);
+    INesCart::parse(&mut rom.as_slice()).unwrap()
+}
+
+#[test]
+fn logs_prg_reads() {
+    let mut cart = cart(&[
+        0xAD, 0x00, 0x81, // LDA $8100
+        0xA2, 0x00,       // LDX #$00
+        0xE8,             // INX
+    ]);
+    cart.start_cdl();
+    let mut nes = NesEmulator::new();
+    nes.cpu.pc = 0xC000;
+    for _ in 0..3 {
+        nes.step(&mut cart).unwrap();
+    }
+    assert_eq!(nes.cpu.reg.a, 0x42);
+
+    use prg_flags::*;
+    let high_bank = 2 << BANK_SHIFT;
+    let cdl = cart.cdl().unwrap();
+    assert_eq!(cdl.prg[0..6], [
+        OPCODE | CODE | high_bank,
+        CODE | high_bank,
+        CODE | high_bank,
+        OPCODE | CODE | high_bank,
+        CODE | high_bank,
+        OPCODE | CODE | high_bank,
+    ]);
+    // The data was read through the mirror at $8000.
+    assert_eq!(cdl.prg[0x0100], DATA);
+    // INX's dummy read of the next byte isn't logged.
+    assert_eq!(cdl.prg[6], 0);
+}
+
+#[test]
+fn logs_chr_reads() {
+    let mut cart = cart(&[
+        0x4C, 0x00, 0x80, // JMP $8000
+    ]);
+    cart.start_cdl();
+    let mut nes = NesEmulator::new();
+    nes.cpu.pc = 0x8000;
+    // Read $0010 and $0011 through PPUDATA. The first read only fills the read buffer.
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x10);
+    bus.read(0x2007);
+    bus.read(0x2007);
+    // Then render a whole frame of tile 0 from the first pattern table.
+    bus.write(0x2001, mask_bits::BACKGROUND | mask_bits::BACKGROUND_LEFT_COLUMN);
+    let frame = nes.ppu.frame;
+    while nes.ppu.frame < frame + 2 {
+        nes.step(&mut cart).unwrap();
+    }
+
+    use chr_flags::*;
+    let cdl = cart.cdl().unwrap();
+    assert_eq!(cdl.chr[0x00..0x10], [RENDERED; 16]);
+    assert_eq!(cdl.chr[0x10..0x13], [READ, READ, 0]);
+    // The second pattern table isn't used.
+    assert!(cdl.chr[0x1000..].iter().all(|&flags| flags == 0));
+}
+
+#[test]
+fn save_and_load() {
+    let mut cart = cart(&[0xEA]);
+    cart.start_cdl();
+    let mut nes = NesEmulator::new();
+    nes.cpu.pc = 0x8000;
+    nes.step(&mut cart).unwrap();
+    let cdl = cart.stop_cdl().unwrap();
+
+    let mut file = Vec::new();
+    cdl.save(&mut file).unwrap();
+    assert_eq!(file.len(), 16384 + 8192);
+    // FCEUX doesn't know the opcode flag.
+    assert_eq!(cdl.prg[0], prg_flags::OPCODE | prg_flags::CODE);
+    assert_eq!(file[0], prg_flags::CODE);
+
+    assert!(cart.cdl().is_none());
+    cart.load_cdl(&mut file.as_slice()).unwrap();
+    let loaded = cart.cdl().unwrap();
+    assert_eq!(loaded.prg[0], prg_flags::CODE);
+    assert_eq!(loaded.prg[1..], cdl.prg[1..]);
+    assert_eq!(loaded.chr, cdl.chr);
+
+    let error = cart.load_cdl(&mut &file[1..]).unwrap_err();
+    assert!(matches!(error, CdlError::WrongSize { expected: 24576, found: 24575 }));
+}
@@ -0,0 +1,496 @@
+use pones::NesEmulator;
+use pones::cart::{INesCart, PpuReadKind};
+use pones::mem::PpuMemMap;
+use pones::ppu::{attribute_bits, ctrl_bits, mask_bits, palette_index, status_bits, SCREEN_WIDTH};
+use pones_6502::Bus;
+
+const PPUCTRL: u16 = 0x2000;
+const PPUMASK: u16 = 0x2001;
+const PPUSTATUS: u16 = 0x2002;
+const OAMADDR: u16 = 0x2003;
+const OAMDATA: u16 = 0x2004;
+const PPUSCROLL: u16 = 0x2005;
+const PPUADDR: u16 = 0x2006;
+const PPUDATA: u16 = 0x2007;
+
+// An NROM cartridge with 16 KB of PRG ROM, all zeros, and 8 KB of CHR ROM counting up.
+// Bit 0 of `flags` selects vertical mirroring.
+fn cart_with_flags(flags: u8) -> INesCart {
+    let mut rom = b"NES\x1A\x01\x01\0\0\0\0\0\0\0\0\0\0".to_vec();
+    rom[6] = flags;
+    rom.resize(16 + 16384, 0);
+    rom.extend((0..8192).map(|i| i as u8));
+    INesCart::parse(&mut rom.as_slice()).unwrap()
+}
+
+fn cart() -> INesCart {
+    cart_with_flags(0)
+}
+
+// An NROM cartridge with 16 KB of PRG ROM, all zeros, and 8 KB of CHR RAM.
+fn chr_ram_cart() -> INesCart {
+    let mut rom = b"NES\x1A\x01\0\0\0\0\0\0\0\0\0\0\0".to_vec();
+    rom.resize(16 + 16384, 0);
+    INesCart::parse(&mut rom.as_slice()).unwrap()
+}
+
+// Write `values` through PPUDATA, starting at `addr`.
+fn write_at(bus: &mut impl Bus, addr: u16, values: &[u8]) {
+    bus.write(PPUADDR, (addr >> 8) as u8);
+    bus.write(PPUADDR, addr as u8);
+    for &value in values {
+        bus.write(PPUDATA, value);
+    }
+}
+
+// Scroll to the top left of the first nametable, after PPUADDR has been used.
+fn reset_scroll(bus: &mut impl Bus) {
+    bus.write(PPUCTRL, 0);
+    bus.write(PPUSCROLL, 0);
+    bus.write(PPUSCROLL, 0);
+}
+
+fn run_frame(nes: &mut NesEmulator, cart: &mut INesCart) {
+    let frame = nes.ppu.frame;
+    let mut bus = PpuMemMap { ppu_mem: &mut nes.ppu_mem, cart, kind: PpuReadKind::Rendering };
+    while nes.ppu.frame == frame {
+        nes.ppu.tick(&mut bus);
+    }
+}
+
+// Run to the start of vertical blanking, before the status flags are cleared for the next frame.
+fn run_to_vblank(nes: &mut NesEmulator, cart: &mut INesCart) {
+    let mut bus = PpuMemMap { ppu_mem: &mut nes.ppu_mem, cart, kind: PpuReadKind::Rendering };
+    while (nes.ppu.scanline, nes.ppu.dot) != (240, 0) {
+        nes.ppu.tick(&mut bus);
+    }
+}
+
+#[test]
+fn ppu_data() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUADDR, 0x21);
+    bus.write(PPUADDR, 0x08);
+    for value in [0x11, 0x22, 0x33] {
+        bus.write(PPUDATA, value);
+    }
+    bus.write(PPUCTRL, ctrl_bits::INCREMENT_32);
+    bus.write(PPUDATA, 0x44);
+
+    // Reads are delayed by the read buffer.
+    bus.write(PPUCTRL, 0);
+    bus.write(PPUADDR, 0x21);
+    bus.write(PPUADDR, 0x08);
+    assert_eq!(bus.read(PPUDATA), 0x00);
+    assert_eq!(bus.read(PPUDATA), 0x11);
+    assert_eq!(bus.read(PPUDATA), 0x22);
+    assert_eq!(nes.ppu.reg.v, 0x210B);
+    assert_eq!(nes.ppu_mem[0x10B], 0x44);
+}
+
+#[test]
+fn ppu_status() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    nes.ppu.reg.ppu_status = status_bits::VBLANK | status_bits::SPRITE_ZERO_HIT;
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    // Leaves the write toggle set, and the low bits of the open bus.
+    bus.write(PPUADDR, 0x3F);
+
+    assert_eq!(bus.read(PPUSTATUS), 0xDF);
+    assert_eq!(bus.read(PPUSTATUS), 0x5F);
+    assert!(!nes.ppu.reg.w);
+}
+
+#[test]
+fn scrolling() {
+    // The example from the "PPU scrolling" page of the NESdev wiki.
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUCTRL, 0x00);
+    bus.read(PPUSTATUS);
+    bus.write(PPUSCROLL, 0x7D);
+    bus.write(PPUSCROLL, 0x5E);
+    bus.write(PPUADDR, 0x3D);
+    bus.write(PPUADDR, 0xF0);
+    let reg = &nes.ppu.reg;
+    assert_eq!((reg.t, reg.v, reg.x, reg.w), (0x3DF0, 0x3DF0, 0b101, false));
+
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUCTRL, 0x03);
+    bus.write(PPUSCROLL, 0x7D);
+    bus.write(PPUSCROLL, 0x5E);
+    let reg = &nes.ppu.reg;
+    assert_eq!((reg.t, reg.x), (0x6D6F, 0b101));
+}
+
+#[test]
+fn oam_data() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(OAMADDR, 0xFE);
+    for value in [0x01, 0x02, 0xFF] {
+        bus.write(OAMDATA, value);
+    }
+    assert_eq!(nes.ppu.oam[0xFE..], [0x01, 0x02]);
+    assert_eq!(nes.ppu.oam[0x00], 0xFF);
+    assert_eq!(nes.ppu.reg.oam_addr, 0x01);
+
+    // Reads don't advance the address, and bits 2-4 of sprite attributes don't exist.
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(OAMADDR, 0x02);
+    bus.write(OAMDATA, 0xFF);
+    bus.write(OAMADDR, 0x02);
+    assert_eq!(bus.read(OAMDATA), 0xE3);
+    assert_eq!(bus.read(OAMDATA), 0xE3);
+}
+
+#[test]
+fn open_bus() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(OAMADDR, 0x5A);
+    assert_eq!(bus.read(PPUCTRL), 0x5A);
+    assert_eq!(bus.read(PPUSCROLL), 0x5A);
+    // Mirrored every 8 bytes.
+    assert_eq!(bus.read(0x3FF8), 0x5A);
+}
+
+#[test]
+fn pattern_tables() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUADDR, 0x12);
+    bus.write(PPUADDR, 0x34);
+    bus.read(PPUDATA);
+    assert_eq!(bus.read(PPUDATA), 0x34);
+    // CHR ROM can't be written.
+    bus.write(PPUDATA, 0xFF);
+    assert_eq!(nes.ppu_mem_map(&mut cart).read(0x1236), 0x36);
+}
+
+#[test]
+fn nametable_mirroring() {
+    for (flags, mirrors) in [(0, [0x2000, 0x2400, 0x2800, 0x2C00]), (1, [0x2000, 0x2800, 0x2400, 0x2C00])] {
+        let mut nes = NesEmulator::new();
+        let mut cart = cart_with_flags(flags);
+        let mut bus = nes.ppu_mem_map(&mut cart);
+        bus.write(mirrors[0] + 0x3C5, 0xAA);
+        bus.write(mirrors[2] + 0x3C5, 0xBB);
+        assert_eq!(bus.read(mirrors[1] + 0x3C5), 0xAA);
+        assert_eq!(bus.read(mirrors[3] + 0x3C5), 0xBB);
+        // $3000-$3EFF mirrors $2000-$2EFF.
+        assert_eq!(bus.read(mirrors[3] + 0x13C5), 0xBB);
+        assert_eq!(nes.ppu_mem[0x3C5], 0xAA);
+        assert_eq!(nes.ppu_mem[0x7C5], 0xBB);
+    }
+}
+
+#[test]
+fn palette_ram() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUADDR, 0x3F);
+    bus.write(PPUADDR, 0x10);
+    for value in [0x0F, 0x11, 0x22, 0x33, 0xFF] {
+        bus.write(PPUDATA, value);
+    }
+    // Through the mirrors, and without the read buffer. The top two bits are open bus,
+    // left by the last write to PPUADDR.
+    bus.write(PPUADDR, 0x3F);
+    bus.write(PPUADDR, 0xE0);
+    assert_eq!(bus.read(PPUDATA), 0xCF);
+    bus.write(PPUADDR, 0x3F);
+    bus.write(PPUADDR, 0x14);
+    assert_eq!(bus.read(PPUDATA), 0x3F);
+
+    assert_eq!(nes.ppu.palette[..0x05], [0x0F, 0, 0, 0, 0xFF]);
+    assert_eq!(nes.ppu.palette[0x11..0x14], [0x11, 0x22, 0x33]);
+    assert_eq!(palette_index(0x3F1C), 0x0C);
+    assert_eq!(palette_index(0x3F1D), 0x1D);
+    // None of it reached VRAM.
+    assert!(nes.ppu_mem.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn frame_timing() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    let mut bus = PpuMemMap { ppu_mem: &mut nes.ppu_mem, cart: &mut cart, kind: PpuReadKind::Rendering };
+    nes.ppu.reg.ppu_ctrl = ctrl_bits::NMI_ENABLE;
+    let mut dots = 0;
+    while (nes.ppu.scanline, nes.ppu.dot) != (241, 2) {
+        nes.ppu.tick(&mut bus);
+        dots += 1;
+    }
+    assert_eq!(dots, 241 * 341 + 2);
+    assert!(nes.ppu.nmi());
+    assert_ne!(nes.ppu.reg.ppu_status & status_bits::VBLANK, 0);
+
+    while (nes.ppu.scanline, nes.ppu.dot) != (261, 2) {
+        nes.ppu.tick(&mut bus);
+    }
+    assert!(!nes.ppu.nmi());
+
+    // With rendering enabled, odd frames are a dot shorter.
+    nes.ppu.reg.ppu_mask = mask_bits::BACKGROUND;
+    let mut frame_dots = Vec::new();
+    for _ in 0..3 {
+        let (frame, mut dots) = (nes.ppu.frame, 0);
+        while nes.ppu.frame == frame {
+            nes.ppu.tick(&mut bus);
+            dots += 1;
+        }
+        frame_dots.push(dots);
+    }
+    assert_eq!(frame_dots[1..], [89341, 89342]);
+}
+
+// Run `BIT PPUSTATUS` and `branch` back to it, from $0300, until the branch falls through.
+// Returns the PPU position after each `BIT`, and the status bits 7 and 6 it saw.
+fn poll_status(nes: &mut NesEmulator, cart: &mut INesCart, branch: u8) -> Vec<((u16, u16), u8)> {
+    let mut bus = nes.cpu_mem_map(cart);
+    for (addr, value) in (0x0300..).zip([0x2C, 0x02, 0x20, branch, 0xFB]) {
+        bus.write(addr, value);
+    }
+    nes.cpu.pc = 0x0300;
+    let mut polls = Vec::new();
+    while nes.cpu.pc == 0x0300 {
+        nes.step(cart).unwrap();
+        let seen = (nes.cpu.reg.negative as u8) << 7 | (nes.cpu.reg.overflow as u8) << 6;
+        polls.push(((nes.ppu.scanline, nes.ppu.dot), seen));
+        nes.step(cart).unwrap();
+    }
+    polls
+}
+
+#[test]
+fn vblank_polling() {
+    let mut nes = NesEmulator::new();
+    let mut cart = cart();
+    // BPL. The read of PPUSTATUS is the last cycle of the BIT, and sees the flag
+    // if the PPU has set it by the end of that cycle.
+    let polls = poll_status(&mut nes, &mut cart, 0x10);
+    let [.., (before, seen_before), (after, seen)] = polls[..] else { panic!("{polls:?}") };
+    assert_eq!(seen_before & status_bits::VBLANK, 0);
+    assert!(before < (241, 2), "{before:?}");
+    assert_ne!(seen & status_bits::VBLANK, 0);
+    assert!(after >= (241, 2), "{after:?}");
+}
+
+#[test]
+fn background() {
+    let mut nes = NesEmulator::new();
+    let mut cart = chr_ram_cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    // Tile 1 has pixels 3, 2, 0, 0, 0, 0, 0, 1 on every row.
+    write_at(&mut bus, 0x0010, &[0b1000_0001; 8]);
+    write_at(&mut bus, 0x0018, &[0b1100_0000; 8]);
+    // In the second column of the first row, with the third background palette.
+    write_at(&mut bus, 0x2001, &[1]);
+    write_at(&mut bus, 0x23C0, &[0b10]);
+    write_at(&mut bus, 0x3F00, &[0x0F]);
+    write_at(&mut bus, 0x3F09, &[0x19, 0x1A, 0x1B]);
+    reset_scroll(&mut bus);
+    bus.write(PPUMASK, mask_bits::BACKGROUND | mask_bits::BACKGROUND_LEFT_COLUMN);
+
+    // The scroll position is copied to `v` at the end of the first frame.
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    let row = |nes: &NesEmulator, y: usize| nes.framebuffer()[y * SCREEN_WIDTH..][..16].to_vec();
+    let mut expected = [0x0F; 16];
+    expected[8..].copy_from_slice(&[0x1B, 0x1A, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x19]);
+    assert_eq!(row(&nes, 0), expected);
+    assert_eq!(row(&nes, 7), expected);
+    assert_eq!(row(&nes, 8), [0x0F; 16]);
+
+    // Scrolled 6 pixels right, with the left column hidden.
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUSCROLL, 6);
+    bus.write(PPUSCROLL, 0);
+    bus.write(PPUMASK, mask_bits::BACKGROUND);
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    let mut expected = [0x0F; 16];
+    expected[9] = 0x19;
+    assert_eq!(row(&nes, 0), expected);
+
+    // Scrolled 4 pixels down, and greyscale.
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    bus.write(PPUSCROLL, 0);
+    bus.write(PPUSCROLL, 4);
+    bus.write(PPUMASK, mask_bits::BACKGROUND | mask_bits::BACKGROUND_LEFT_COLUMN | mask_bits::GREYSCALE);
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    assert_eq!(row(&nes, 3)[8], 0x10);
+    assert_eq!(row(&nes, 4), [0x00; 16]);
+}
+
+const ALL_LAYERS: u8 = mask_bits::BACKGROUND | mask_bits::BACKGROUND_LEFT_COLUMN | mask_bits::SPRITES | mask_bits::SPRITES_LEFT_COLUMN;
+
+// A cartridge with CHR RAM, where tile 1 is solid colour 1 and tiles 2 and 3 have a single
+// pixel of colour 3 at their top left. The second sprite palette is set up, and OAM is
+// filled with sprites below the screen.
+fn sprite_setup(nes: &mut NesEmulator) -> INesCart {
+    let mut cart = chr_ram_cart();
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    write_at(&mut bus, 0x0010, &[0xFF; 8]);
+    write_at(&mut bus, 0x0020, &[0x80, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+    write_at(&mut bus, 0x0030, &[0x80, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+    write_at(&mut bus, 0x3F00, &[0x0F]);
+    write_at(&mut bus, 0x3F15, &[0x25, 0x26, 0x27]);
+    reset_scroll(&mut bus);
+    nes.ppu.oam = [0xFF; 256];
+    cart
+}
+
+// The positions of the pixels in the frame that aren't the backdrop colour.
+fn drawn_pixels(nes: &NesEmulator) -> Vec<(usize, usize, u8)> {
+    let pixels = nes.framebuffer().iter().enumerate().filter(|&(_, &colour)| colour != 0x0F);
+    pixels.map(|(i, &colour)| (i % SCREEN_WIDTH, i / SCREEN_WIDTH, colour)).collect()
+}
+
+#[test]
+fn sprites() {
+    let mut nes = NesEmulator::new();
+    let mut cart = sprite_setup(&mut nes);
+    use attribute_bits::*;
+    nes.ppu.oam[..16].copy_from_slice(&[
+        9, 2, 1, 20,
+        9, 2, 1 | FLIP_HORIZONTAL, 40,
+        9, 2, 1 | FLIP_VERTICAL, 60,
+        // Hidden in the left column.
+        9, 2, 1, 4,
+    ]);
+    nes.ppu.reg.ppu_mask = mask_bits::SPRITES;
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    assert_eq!(drawn_pixels(&nes), [(20, 10, 0x27), (47, 10, 0x27), (60, 17, 0x27)]);
+
+    // 8x16 sprites take their pattern table from the tile index.
+    let mut bus = nes.ppu_mem_map(&mut cart);
+    bus.write(0x1020, 0x40);
+    bus.write(0x1037, 0x01);
+    nes.ppu.oam = [0xFF; 256];
+    nes.ppu.oam[..8].copy_from_slice(&[29, 2, 1, 100, 49, 3, 1 | FLIP_VERTICAL, 120]);
+    nes.ppu.reg.ppu_ctrl = ctrl_bits::SPRITE_8X16;
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    assert_eq!(drawn_pixels(&nes), [(100, 30, 0x27), (100, 38, 0x27), (127, 50, 0x25), (121, 65, 0x25)]);
+}
+
+#[test]
+fn sprite_priority() {
+    let mut nes = NesEmulator::new();
+    let mut cart = sprite_setup(&mut nes);
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    // A background tile of colour 1 at (8, 8), using the first palette.
+    write_at(&mut bus, 0x2021, &[1]);
+    write_at(&mut bus, 0x3F01, &[0x01]);
+    reset_scroll(&mut bus);
+    use attribute_bits::*;
+    nes.ppu.oam[..12].copy_from_slice(&[
+        // Behind the background, but in front of the next sprite, which it hides.
+        7, 2, 1 | BEHIND_BACKGROUND, 8,
+        7, 2, 2, 8,
+        7, 2, 1, 9,
+    ]);
+    nes.ppu.reg.ppu_mask = ALL_LAYERS;
+    run_frame(&mut nes, &mut cart);
+    run_frame(&mut nes, &mut cart);
+    let row: Vec<u8> = nes.framebuffer()[8 * SCREEN_WIDTH..][7..11].to_vec();
+    assert_eq!(row, [0x0F, 0x01, 0x27, 0x01]);
+}
+
+#[test]
+fn sprite_zero_hit() {
+    let mut nes = NesEmulator::new();
+    let mut cart = sprite_setup(&mut nes);
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    // Background tiles of colour 1 at (0, 8) and (8, 8).
+    write_at(&mut bus, 0x2020, &[1, 1]);
+    reset_scroll(&mut bus);
+    nes.ppu.oam[..4].copy_from_slice(&[9, 2, 1, 12]);
+    nes.ppu.reg.ppu_mask = ALL_LAYERS;
+    run_frame(&mut nes, &mut cart);
+
+    // Set on the dot the overlapping pixel is drawn, and cleared on the pre-render scanline.
+    let hit = |nes: &NesEmulator| nes.ppu.reg.ppu_status & status_bits::SPRITE_ZERO_HIT != 0;
+    let mut bus = PpuMemMap { ppu_mem: &mut nes.ppu_mem, cart: &mut cart, kind: PpuReadKind::Rendering };
+    let mut position = (0, 0);
+    while nes.ppu.reg.ppu_status & status_bits::SPRITE_ZERO_HIT == 0 && nes.ppu.scanline < 240 {
+        position = (nes.ppu.scanline, nes.ppu.dot);
+        nes.ppu.tick(&mut bus);
+    }
+    assert_eq!(position, (10, 13));
+    run_frame(&mut nes, &mut cart);
+    assert!(!hit(&nes));
+
+    // Not in the hidden left column, nor with the sprite over a transparent pixel.
+    nes.ppu.oam[..4].copy_from_slice(&[9, 2, 1, 4]);
+    nes.ppu.reg.ppu_mask = ALL_LAYERS & !mask_bits::SPRITES_LEFT_COLUMN;
+    run_to_vblank(&mut nes, &mut cart);
+    assert!(!hit(&nes));
+    run_frame(&mut nes, &mut cart);
+    nes.ppu.oam[..4].copy_from_slice(&[9, 2, 1, 20]);
+    nes.ppu.reg.ppu_mask = ALL_LAYERS;
+    run_to_vblank(&mut nes, &mut cart);
+    assert!(!hit(&nes));
+}
+
+#[test]
+fn sprite_zero_hit_polling() {
+    let mut nes = NesEmulator::new();
+    let mut cart = sprite_setup(&mut nes);
+    let mut bus = nes.cpu_mem_map(&mut cart);
+    write_at(&mut bus, 0x2020, &[1, 1]);
+    reset_scroll(&mut bus);
+    nes.ppu.oam[..4].copy_from_slice(&[9, 2, 1, 12]);
+    nes.ppu.reg.ppu_mask = ALL_LAYERS;
+    run_frame(&mut nes, &mut cart);
+
+    // BVC. The hit is set on the dot at (10, 13), so the first BIT to see it is the
+    // first whose read of PPUSTATUS ends after that dot.
+    let polls = poll_status(&mut nes, &mut cart, 0x50);
+    let [.., (before, seen_before), (after, seen)] = polls[..] else { panic!("{polls:?}") };
+    assert_eq!(seen_before & status_bits::SPRITE_ZERO_HIT, 0);
+    assert!(before < (10, 14), "{before:?}");
+    assert_ne!(seen & status_bits::SPRITE_ZERO_HIT, 0);
+    assert!(after >= (10, 14), "{after:?}");
+}
+
+#[test]
+fn sprite_overflow() {
+    let mut nes = NesEmulator::new();
+    let mut cart = sprite_setup(&mut nes);
+    for (i, sprite) in nes.ppu.oam.chunks_mut(4).take(9).enumerate() {
+        sprite.copy_from_slice(&[9, 2, 1, i as u8 * 8]);
+    }
+    nes.ppu.reg.ppu_mask = mask_bits::SPRITES | mask_bits::SPRITES_LEFT_COLUMN;
+    let overflow = |nes: &NesEmulator| nes.ppu.reg.ppu_status & status_bits::SPRITE_OVERFLOW != 0;
+    run_frame(&mut nes, &mut cart);
+    run_to_vblank(&mut nes, &mut cart);
+    assert!(overflow(&nes));
+    // Only 8 sprites are drawn.
+    let xs: Vec<usize> = drawn_pixels(&nes).iter().map(|&(x, _, _)| x).collect();
+    assert_eq!(xs, [0, 8, 16, 24, 32, 40, 48, 56]);
+
+    // With the 9th sprite off the scanline, the PPU checks the 10th sprite's tile
+    // index as a Y coordinate.
+    nes.ppu.oam[32] = 0xF0;
+    nes.ppu.oam[37] = 12;
+    run_frame(&mut nes, &mut cart);
+    run_to_vblank(&mut nes, &mut cart);
+    assert!(overflow(&nes));
+    nes.ppu.oam[37] = 0xF0;
+    run_frame(&mut nes, &mut cart);
+    run_to_vblank(&mut nes, &mut cart);
+    assert!(!overflow(&nes));
+}